@@ -0,0 +1,316 @@
+use crate::bitboard::{count_bits, get_ls1b_index, pop_ls1b};
+use crate::defs::{Bitboard, Side};
+use crate::magic::*;
+use crate::pieces::{king::*, knight::*, pawn::*};
+use crate::position::Position;
+use std::sync::LazyLock;
+
+// Precomputed attack lookup tables, initialized once on first use
+//
+// https://www.chessprogramming.org/Magic_Bitboards#Fancy
+struct AttackTables {
+    pawn: [[u64; 64]; 2],
+    knight: [u64; 64],
+    king: [u64; 64],
+    rook: SliderTable,
+    bishop: SliderTable,
+}
+
+struct SliderTable {
+    masks: [u64; 64],
+    magics: [u64; 64],
+    relevant_bits: [u32; 64],
+    attacks: Vec<u64>,
+    size: usize,
+}
+
+impl SliderTable {
+    fn new(slider: Slider, magics: [u64; 64]) -> Self {
+        let mut masks = [0; 64];
+        let mut relevant_bits = [0; 64];
+        for square in 0..64 {
+            masks[square] = slider.occupancy(square as u8);
+            relevant_bits[square] = count_bits(masks[square]);
+        }
+
+        let size = 1 << relevant_bits.iter().max().unwrap();
+        let mut attacks = vec![0; 64 * size];
+        for square in 0..64 {
+            for index in 0..(1 << relevant_bits[square]) {
+                let occupancy = set_occupancy(index, masks[square]);
+                let magic_index = get_magic_index(occupancy, magics[square], relevant_bits[square]);
+                attacks[square * size + magic_index] = slider.attacks(square as u8, occupancy);
+            }
+        }
+
+        Self { masks, magics, relevant_bits, attacks, size }
+    }
+
+    #[inline]
+    fn get(&self, square: u8, occupancy: u64) -> u64 {
+        let square = square as usize;
+        let occupancy = occupancy & self.masks[square];
+        let index = get_magic_index(occupancy, self.magics[square], self.relevant_bits[square]);
+        self.attacks[square * self.size + index]
+    }
+}
+
+static TABLES: LazyLock<AttackTables> = LazyLock::new(|| AttackTables {
+    pawn: get_pawn_attacks_table(),
+    knight: get_knight_attacks_table(),
+    king: get_king_attacks_table(),
+    rook: SliderTable::new(Slider::Rook, ROOK_MAGICS),
+    bishop: SliderTable::new(Slider::Bishop, BISHOP_MAGICS),
+});
+
+// Forces the initialization of attack tables, so that it does not happen
+// during the first time-critical lookup
+pub fn init() {
+    LazyLock::force(&TABLES);
+}
+
+#[inline]
+pub fn pawn_attacks(side: Side, square: u8) -> u64 {
+    TABLES.pawn[side as usize][square as usize]
+}
+
+#[inline]
+pub fn knight_attacks(square: u8) -> u64 {
+    TABLES.knight[square as usize]
+}
+
+#[inline]
+pub fn king_attacks(square: u8) -> u64 {
+    TABLES.king[square as usize]
+}
+
+#[inline]
+pub fn rook_attacks(square: u8, occupancy: u64) -> u64 {
+    TABLES.rook.get(square, occupancy)
+}
+
+#[inline]
+pub fn bishop_attacks(square: u8, occupancy: u64) -> u64 {
+    TABLES.bishop.get(square, occupancy)
+}
+
+#[inline]
+pub fn queen_attacks(square: u8, occupancy: u64) -> u64 {
+    rook_attacks(square, occupancy) | bishop_attacks(square, occupancy)
+}
+
+impl Position {
+    // Checks whether any piece of the given side attacks the square
+    pub fn is_square_attacked(&self, square: u8, side: Side) -> bool {
+        let offset = side.piece_offset();
+        let occupancy = self.bitboards[Bitboard::ALL_PIECES];
+        let bitboard = |index: usize| self.bitboards[index + offset];
+
+        let queens = bitboard(Bitboard::WHITE_QUEENS);
+
+        pawn_attacks(!side, square) & bitboard(Bitboard::WHITE_PAWNS) != 0
+            || knight_attacks(square) & bitboard(Bitboard::WHITE_KNIGHTS) != 0
+            || king_attacks(square) & bitboard(Bitboard::WHITE_KING) != 0
+            || bishop_attacks(square, occupancy) & (bitboard(Bitboard::WHITE_BISHOPS) | queens) != 0
+            || rook_attacks(square, occupancy) & (bitboard(Bitboard::WHITE_ROOKS) | queens) != 0
+    }
+
+    // Checks whether the king of the side to move is attacked
+    pub fn in_check(&self) -> bool {
+        let king = self.bitboards[Bitboard::WHITE_KING + self.side_to_move.piece_offset()];
+        king != 0 && self.is_square_attacked(get_ls1b_index(king), !self.side_to_move)
+    }
+
+    // Returns pieces of both sides attacking the square, with sliding attacks
+    // blocked by the given occupancy instead of the board one, which allows
+    // to look through pieces already removed from the square
+    pub fn attackers_to(&self, square: u8, occupancy: u64) -> u64 {
+        let bitboards = &self.bitboards;
+        let bishops = bitboards[Bitboard::WHITE_BISHOPS]
+            | bitboards[Bitboard::BLACK_BISHOPS]
+            | bitboards[Bitboard::WHITE_QUEENS]
+            | bitboards[Bitboard::BLACK_QUEENS];
+        let rooks = bitboards[Bitboard::WHITE_ROOKS]
+            | bitboards[Bitboard::BLACK_ROOKS]
+            | bitboards[Bitboard::WHITE_QUEENS]
+            | bitboards[Bitboard::BLACK_QUEENS];
+
+        (pawn_attacks(Side::Black, square) & bitboards[Bitboard::WHITE_PAWNS])
+            | (pawn_attacks(Side::White, square) & bitboards[Bitboard::BLACK_PAWNS])
+            | (knight_attacks(square)
+                & (bitboards[Bitboard::WHITE_KNIGHTS] | bitboards[Bitboard::BLACK_KNIGHTS]))
+            | (king_attacks(square)
+                & (bitboards[Bitboard::WHITE_KING] | bitboards[Bitboard::BLACK_KING]))
+            | (bishop_attacks(square, occupancy) & bishops)
+            | (rook_attacks(square, occupancy) & rooks)
+    }
+
+    // Returns enemy pieces giving check to the king of the side to move
+    pub fn checkers(&self) -> u64 {
+        let side = self.side_to_move;
+        let king = self.bitboards[Bitboard::WHITE_KING + side.piece_offset()];
+        if king == 0 {
+            return 0;
+        }
+
+        let occupancy = self.bitboards[Bitboard::ALL_PIECES];
+        self.attackers_to(get_ls1b_index(king), occupancy) & self.bitboards[(!side).pieces_index()]
+    }
+
+    // Returns pieces of the given side which cannot leave the line between
+    // their king and an enemy slider without exposing the king
+    //
+    // https://www.chessprogramming.org/Pin
+    pub fn pinned_pieces(&self, side: Side) -> u64 {
+        let king = self.bitboards[Bitboard::WHITE_KING + side.piece_offset()];
+        if king == 0 {
+            return 0;
+        }
+
+        let king_square = get_ls1b_index(king);
+        let occupancy = self.bitboards[Bitboard::ALL_PIECES];
+        let enemy = |index: usize| self.bitboards[index + (!side).piece_offset()];
+        let queens = enemy(Bitboard::WHITE_QUEENS);
+
+        let lines = [
+            (rook_attacks as fn(u8, u64) -> u64, enemy(Bitboard::WHITE_ROOKS) | queens),
+            (bishop_attacks, enemy(Bitboard::WHITE_BISHOPS) | queens),
+        ];
+
+        let mut pinned = 0;
+        for (attacks, sliders) in lines {
+            // Own pieces first on the king rays, and sliders seen through them
+            let from_king = attacks(king_square, occupancy);
+            let blockers = from_king & self.bitboards[side.pieces_index()];
+            let mut pinners = attacks(king_square, occupancy ^ blockers) & sliders & !from_king;
+
+            while pinners != 0 {
+                let pinner = pop_ls1b(&mut pinners);
+                pinned |= attacks(pinner, occupancy) & from_king & blockers;
+            }
+        }
+
+        pinned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::Square::*;
+    use crate::pieces::{bishop::get_bishop_attacks, rook::get_rook_attacks};
+    use crate::test::*;
+
+    fn verify_slider_table(slider: Slider, lookup: fn(u8, u64) -> u64) {
+        for square in 0..64 {
+            let mask = slider.occupancy(square);
+            for index in 0..(1 << count_bits(mask)) {
+                let occupancy = set_occupancy(index, mask);
+                assert_eq!(lookup(square, occupancy), slider.attacks(square, occupancy));
+            }
+        }
+    }
+
+    #[test]
+    fn rook_table_matches_slow_attacks() {
+        verify_slider_table(Slider::Rook, rook_attacks);
+    }
+
+    #[test]
+    fn bishop_table_matches_slow_attacks() {
+        verify_slider_table(Slider::Bishop, bishop_attacks);
+    }
+
+    #[test]
+    fn ignores_irrelevant_occupancy() {
+        let occupancy = bitboard_with(vec![D6, A4, G4, D2, H4, D8, F6, B2]);
+
+        assert_eq!(rook_attacks(D4 as u8, occupancy), get_rook_attacks(D4 as u8, occupancy));
+        assert_eq!(bishop_attacks(D4 as u8, occupancy), get_bishop_attacks(D4 as u8, occupancy));
+    }
+
+    #[test]
+    fn combines_queen_attacks() {
+        let occupancy = bitboard_with(vec![D6, F6, B2]);
+
+        assert_eq!(
+            queen_attacks(D4 as u8, occupancy),
+            get_rook_attacks(D4 as u8, occupancy) | get_bishop_attacks(D4 as u8, occupancy)
+        );
+    }
+
+    #[test]
+    fn detects_attacked_squares() {
+        let position = Position::from_fen("4k3/8/8/3p4/8/5n2/8/R3K3 w - - 0 1").unwrap();
+
+        assert!(position.is_square_attacked(E4 as u8, Side::Black));
+        assert!(position.is_square_attacked(C4 as u8, Side::Black));
+        assert!(position.is_square_attacked(G1 as u8, Side::Black));
+        assert!(!position.is_square_attacked(D3 as u8, Side::Black));
+        assert!(position.is_square_attacked(A8 as u8, Side::White));
+        assert!(!position.is_square_attacked(A8 as u8, Side::Black));
+        assert!(position.is_square_attacked(D7 as u8, Side::Black));
+    }
+
+    #[test]
+    fn detects_check() {
+        assert!(!Position::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap().in_check());
+        assert!(Position::from_fen("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").unwrap().in_check());
+    }
+
+    #[test]
+    fn finds_attackers() {
+        let position = Position::from_fen("4k3/8/2n5/3p4/4R3/8/1B6/4K3 w - - 0 1").unwrap();
+        let occupancy = position.bitboards[Bitboard::ALL_PIECES];
+
+        verify_bitboard(position.attackers_to(E5 as u8, occupancy), vec![E4, C6, B2]);
+        verify_bitboard(position.attackers_to(C4 as u8, occupancy), vec![D5, E4]);
+        verify_bitboard(position.attackers_to(D4 as u8, occupancy), vec![C6, B2, E4]);
+        verify_bitboard(position.attackers_to(E8 as u8, occupancy), vec![E4]);
+        verify_bitboard(position.attackers_to(A8 as u8, occupancy), vec![]);
+    }
+
+    #[test]
+    fn finds_attackers_through_removed_pieces() {
+        let position = Position::from_fen("3rk3/8/8/8/3P4/8/8/3RK3 w - - 0 1").unwrap();
+        let occupancy = position.bitboards[Bitboard::ALL_PIECES];
+
+        verify_bitboard(position.attackers_to(D5 as u8, occupancy), vec![D8]);
+        let occupancy = occupancy & !bitboard_with(vec![D4]);
+        verify_bitboard(position.attackers_to(D5 as u8, occupancy), vec![D8, D1]);
+    }
+
+    #[test]
+    fn finds_checkers() {
+        let position = Position::from_fen("4k3/8/3N4/8/8/8/8/4RK2 b - - 0 1").unwrap();
+        verify_bitboard(position.checkers(), vec![D6, E1]);
+        assert!(position.in_check());
+
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        verify_bitboard(position.checkers(), vec![]);
+        assert!(!position.in_check());
+    }
+
+    #[test]
+    fn finds_pinned_pieces() {
+        let position = Position::from_fen("k3r3/8/5n2/1b6/2P1R3/8/2Q1K1Nr/8 w - - 0 1").unwrap();
+
+        verify_bitboard(position.pinned_pieces(Side::White), vec![C4, E4, G2]);
+        verify_bitboard(position.pinned_pieces(Side::Black), vec![]);
+
+        // Two pieces between the king and the slider are not pinned
+        let position = Position::from_fen("k3r3/8/8/4P3/4R3/8/8/4K3 w - - 0 1").unwrap();
+        verify_bitboard(position.pinned_pieces(Side::White), vec![]);
+
+        let position = Position::from_fen("4k3/8/8/8/8/2b5/3N4/4K3 w - - 0 1").unwrap();
+        verify_bitboard(position.pinned_pieces(Side::White), vec![D2]);
+        verify_bitboard(position.pinned_pieces(Side::Black), vec![]);
+    }
+
+    #[test]
+    fn looks_up_leaper_attacks() {
+        verify_bitboard(knight_attacks(A6 as u8), vec![B8, C7, C5, B4]);
+        verify_bitboard(king_attacks(A1 as u8), vec![A2, B2, B1]);
+        verify_bitboard(pawn_attacks(Side::White, E4 as u8), vec![D5, F5]);
+    }
+}
@@ -49,6 +49,19 @@ pub fn get_ls1b_index(bitboard: u64) -> u8 {
     bitboard.trailing_zeros() as u8
 }
 
+// Removes the least significant 1st bit from a bitboard and returns its index
+#[inline]
+pub fn pop_ls1b(bitboard: &mut u64) -> u8 {
+    let square = get_ls1b_index(*bitboard);
+    *bitboard &= *bitboard - 1;
+    square
+}
+
+#[inline]
+pub fn count_bits(bitboard: u64) -> u32 {
+    bitboard.count_ones()
+}
+
 pub fn print_bitboard(bitboard: u64) {
     println!("   a b c d e f g h");
     for rank in 0..8 {
@@ -129,6 +142,21 @@ mod tests {
         get_ls1b_index(0);
     }
 
+    #[test]
+    fn pops_ls1b() {
+        let mut bitboard = bit_from_sq(E4 as u8) | bit_from_sq(H1 as u8);
+        assert_eq!(pop_ls1b(&mut bitboard), E4 as u8);
+        assert_eq!(pop_ls1b(&mut bitboard), H1 as u8);
+        assert_eq!(bitboard, 0);
+    }
+
+    #[test]
+    fn counts_bits() {
+        assert_eq!(count_bits(0), 0);
+        assert_eq!(count_bits(0b1011), 3);
+        assert_eq!(count_bits(u64::MAX), 64);
+    }
+
     #[test]
     fn sums_bitboards() {
         assert_eq!(sum_bitboards(&[0b1100, 0b0100, 0b1]), 0b1101);
@@ -0,0 +1,176 @@
+use crate::constants::PIECE_CHARS;
+use crate::defs::Bitboard;
+use crate::position::Position;
+
+// Number of Chess960 start positions
+pub const CHESS960_POSITIONS: u16 = 960;
+// Scharnagl index of the standard start position
+pub const STANDARD_INDEX: u16 = 518;
+
+// Placements of two knights on five empty squares, by the knight digit of the index
+const KNIGHTS: [(usize, usize); 10] =
+    [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+
+// Returns the back rank of the Chess960 start position with the given
+// Scharnagl index, as White bitboard indices from the a-file to the h-file
+//
+// https://www.chessprogramming.org/Reinhard_Scharnagl#Chess960Numbering
+pub fn back_rank(index: u16) -> Option<[usize; 8]> {
+    if index >= CHESS960_POSITIONS {
+        return None;
+    }
+
+    let mut rank = [None; 8];
+    let mut n = index as usize;
+
+    // Bishops on opposite colors, the first one on b, d, f or h
+    rank[n % 4 * 2 + 1] = Some(Bitboard::WHITE_BISHOPS);
+    n /= 4;
+    rank[n % 4 * 2] = Some(Bitboard::WHITE_BISHOPS);
+    n /= 4;
+    place_on_empty(&mut rank, n % 6, Bitboard::WHITE_QUEENS);
+    n /= 6;
+
+    // The second knight goes first, so that it does not shift the other one
+    let (first, second) = KNIGHTS[n];
+    place_on_empty(&mut rank, second, Bitboard::WHITE_KNIGHTS);
+    place_on_empty(&mut rank, first, Bitboard::WHITE_KNIGHTS);
+
+    // The king stands between the rooks on the remaining squares
+    for piece in [Bitboard::WHITE_ROOKS, Bitboard::WHITE_KING, Bitboard::WHITE_ROOKS] {
+        place_on_empty(&mut rank, 0, piece);
+    }
+
+    Some(rank.map(|piece| piece.unwrap()))
+}
+
+// Returns the Scharnagl index of a back rank given as White bitboard indices,
+// or None if it is not a Chess960 start arrangement
+pub fn back_rank_index(rank: &[usize; 8]) -> Option<u16> {
+    let files = |piece: usize| (0..8).filter(move |&file| rank[file] == piece);
+
+    let light = files(Bitboard::WHITE_BISHOPS).find(|file| file % 2 == 1)? / 2;
+    let dark = files(Bitboard::WHITE_BISHOPS).find(|file| file % 2 == 0)? / 2;
+
+    // Pieces are counted on the squares which were empty when they were placed
+    let empty_before = |file: usize, placed: &[usize]| {
+        (0..file).filter(|&before| !placed.contains(&rank[before])).count()
+    };
+    let queen = files(Bitboard::WHITE_QUEENS)
+        .map(|file| empty_before(file, &[Bitboard::WHITE_BISHOPS]))
+        .next()?;
+
+    let knights: Vec<usize> = files(Bitboard::WHITE_KNIGHTS)
+        .map(|file| empty_before(file, &[Bitboard::WHITE_BISHOPS, Bitboard::WHITE_QUEENS]))
+        .collect();
+    let knights = KNIGHTS.iter().position(|&(first, second)| knights == [first, second])?;
+
+    let index = (((knights * 6 + queen) * 4 + dark) * 4 + light) as u16;
+    (back_rank(index)? == *rank).then_some(index)
+}
+
+// Puts the piece on the n-th still empty square of the rank
+fn place_on_empty(rank: &mut [Option<usize>; 8], n: usize, piece: usize) {
+    let file = (0..8).filter(|&file| rank[file].is_none()).nth(n).unwrap();
+    rank[file] = Some(piece);
+}
+
+impl Position {
+    // Creates the Chess960 start position with the given Scharnagl index
+    pub fn from_chess960(index: u16) -> Option<Self> {
+        Self::from_double_chess960(index, index)
+    }
+
+    // Creates a Double Chess960 start position, in which White and Black
+    // get independent back rank arrangements
+    pub fn from_double_chess960(white: u16, black: u16) -> Option<Self> {
+        let (white, black) = (back_rank(white)?, back_rank(black)?);
+        let rank_str =
+            |rank: [usize; 8]| -> String { rank.iter().map(|&piece| PIECE_CHARS[piece]).collect() };
+        // Castling rights are given by the files of the rooks
+        let rook_files = |rank: [usize; 8]| -> String {
+            (0..8u8)
+                .rev()
+                .filter(|&file| rank[file as usize] == Bitboard::WHITE_ROOKS)
+                .map(|file| (b'A' + file) as char)
+                .collect()
+        };
+
+        let fen = format!(
+            "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w {}{} - 0 1",
+            rank_str(black).to_lowercase(),
+            rank_str(white),
+            rook_files(white),
+            rook_files(black).to_lowercase()
+        );
+        let position = Self::from_fen(&fen).expect("invalid Chess960 position");
+        // Castling is encoded as in Chess960 even in the standard placement
+        Some(Self { chess960: true, ..position })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::STARTING_FEN;
+
+    fn rank_str(index: u16) -> String {
+        back_rank(index).unwrap().iter().map(|&piece| PIECE_CHARS[piece]).collect()
+    }
+
+    #[test]
+    fn generates_back_ranks() {
+        assert_eq!(rank_str(0), "BBQNNRKR");
+        assert_eq!(rank_str(STANDARD_INDEX), "RNBQKBNR");
+        assert_eq!(rank_str(959), "RKRNNQBB");
+        assert_eq!(back_rank(CHESS960_POSITIONS), None);
+    }
+
+    #[test]
+    fn standard_index_is_starting_position() {
+        let position = Position::from_chess960(STANDARD_INDEX).unwrap();
+
+        assert_eq!(position.to_fen(), STARTING_FEN);
+        assert!(position.chess960);
+        assert_eq!(position.legal_moves().len(), 20);
+    }
+
+    #[test]
+    fn finds_index_of_back_ranks() {
+        let mut ranks = std::collections::HashSet::new();
+
+        for index in 0..CHESS960_POSITIONS {
+            let rank = back_rank(index).unwrap();
+            assert_eq!(back_rank_index(&rank), Some(index));
+            assert!(ranks.insert(rank));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_back_ranks() {
+        let rank = |rank_str: &str| {
+            let mut rank = [0; 8];
+            for (file, c) in rank_str.chars().enumerate() {
+                rank[file] = PIECE_CHARS.iter().position(|&piece| piece == c).unwrap();
+            }
+            rank
+        };
+
+        assert_eq!(back_rank_index(&rank("RNBQKBNR")), Some(STANDARD_INDEX));
+        // Bishops on the same color
+        assert_eq!(back_rank_index(&rank("RBNBKQNR")), None);
+        // King outside the rooks
+        assert_eq!(back_rank_index(&rank("KRBQRBNN")), None);
+        assert_eq!(back_rank_index(&rank("RNBQKBNN")), None);
+    }
+
+    #[test]
+    fn creates_double_chess960_positions() {
+        let position = Position::from_double_chess960(0, 959).unwrap();
+
+        assert_eq!(position.to_fen(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert_eq!(position.to_shredder_fen().split(' ').nth(2), Some("HFca"));
+        assert!(position.validate().is_ok());
+        assert_eq!(Position::from_double_chess960(0, 960), None);
+    }
+}
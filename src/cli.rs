@@ -4,34 +4,52 @@ use crate::{
     defs::{Castling, Square},
     position::Position,
 };
+use std::time::Instant;
 
 impl Position {
     pub fn print_board(&self) {
+        println!("{}", self.board_string());
+    }
+
+    // Formats the board with the state of the position, as printed by print_board
+    pub fn board_string(&self) -> String {
+        let mut board = String::new();
+
         for rank in 0..8 {
-            print!("{} ", rank);
+            board.push_str(&format!("{} ", rank));
 
             for file in 0..8 {
-                let mut piece_index: i8 = -1;
-                for piece in 0..12 {
-                    if get_bit(self.bitboards[piece], sq(rank, file)) != 0 {
-                        piece_index = piece as i8;
-                        break;
-                    }
-                }
-
                 let piece_char =
-                    if piece_index != -1 { PIECE_CHARS[piece_index as usize] } else { '.' };
-                print!(" {}", piece_char);
+                    self.piece_at(sq(rank, file)).map_or('.', |piece| PIECE_CHARS[piece]);
+                board.push_str(&format!(" {}", piece_char));
             }
 
-            println!();
+            board.push('\n');
+        }
+
+        board.push_str("\n   a b c d e f g h\n\n");
+        board.push_str(&format!(
+            "Fifty clock: {}  Moves: {}\n",
+            self.fifty_move_count,
+            self.halfmove_count / 2
+        ));
+        board.push_str(&format!("Castling rights: {}\n", castling_rights(self.castling_rights)));
+        board.push_str(&format!("En passant: {}\n", en_passant_square(self.en_passant_square)));
+        board.push_str(&format!("Side to move: {}", self.side_to_move));
+        board
+    }
+
+    // Prints perft node counts split by the root moves
+    pub fn print_divide(&mut self, depth: u32) {
+        let start = Instant::now();
+        let divide = self.divide(depth);
+
+        for (m, nodes) in &divide {
+            println!("{}: {}", m, nodes);
         }
 
-        println!("\n   a b c d e f g h\n");
-        println!("Fifty clock: {}  Moves: {}", self.fifty_move_count, self.halfmove_count / 2);
-        println!("Castling rights: {}", castling_rights(self.castling_rights));
-        println!("En passant: {}", en_passant_square(self.en_passant_square));
-        println!("Side to move: {}", self.side_to_move);
+        println!("\nNodes searched: {}", divide.iter().map(|(_, nodes)| nodes).sum::<u64>());
+        println!("Time: {} ms", start.elapsed().as_millis());
     }
 }
 
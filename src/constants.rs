@@ -21,3 +21,11 @@ pub const GH_FILE: u64 = G_FILE | H_FILE;
 
 pub const EMPTY_FEN: &str = "8/8/8/8/8/8/8/8 w - - ";
 pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+pub const RANK_8: u64 = 255;
+pub const RANK_7: u64 = 65280;
+pub const RANK_2: u64 = 71776119061217280;
+pub const RANK_1: u64 = 18374686479671623680;
+
+pub const LIGHT_SQUARES: u64 = 12273903644374837845;
+pub const DARK_SQUARES: u64 = !LIGHT_SQUARES;
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, ops};
 
 #[rustfmt::skip]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -14,7 +14,7 @@ pub enum Square {
   NoSquare
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum Piece {
     WhiteKing,
     WhiteQueen,
@@ -30,12 +30,37 @@ pub enum Piece {
     BlackPawn,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum Side {
     White,
     Black,
 }
 
+impl Side {
+    // Offset of the side's piece bitboards in the position bitboards array
+    #[inline]
+    pub fn piece_offset(self) -> usize {
+        self as usize * 6
+    }
+
+    // Index of the bitboard holding all pieces of the side
+    #[inline]
+    pub fn pieces_index(self) -> usize {
+        Bitboard::WHITE_PIECES + self as usize
+    }
+}
+
+impl ops::Not for Side {
+    type Output = Side;
+
+    fn not(self) -> Side {
+        match self {
+            Side::White => Side::Black,
+            Side::Black => Side::White,
+        }
+    }
+}
+
 impl fmt::Display for Side {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -71,7 +96,8 @@ impl From<Piece> for Side {
 }
 
 // Castling rights are represented by a 4 bit unsigned integer
-// Each bit represents a single castling right
+// Each bit represents a single castling right, while the squares of the
+// castling rooks are kept in the position to support Chess960
 //
 // https://www.chessprogramming.org/Castling_Rights
 pub type CastlingRights = u8;
@@ -0,0 +1,650 @@
+use crate::attacks::{
+    bishop_attacks, king_attacks, knight_attacks, pawn_attacks, queen_attacks, rook_attacks,
+};
+use crate::bitboard::{coords, count_bits, pop_ls1b};
+use crate::constants::A_FILE;
+use crate::defs::{Bitboard, Side};
+use crate::position::Position;
+use std::{fmt, ops};
+
+// Pair of midgame and endgame scores, interpolated by the game phase
+//
+// https://www.chessprogramming.org/Tapered_Eval
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
+
+    // Interpolates between the midgame and endgame score
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl ops::Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl ops::Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl ops::AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl ops::Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, factor: i32) -> Score {
+        Score::new(self.mg * factor, self.eg * factor)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Term {
+    Material,
+    PieceSquares,
+    Pawns,
+    Mobility,
+    KingSafety,
+    BishopPair,
+    RookFiles,
+}
+
+impl Term {
+    pub const ALL: [Term; 7] = [
+        Term::Material,
+        Term::PieceSquares,
+        Term::Pawns,
+        Term::Mobility,
+        Term::KingSafety,
+        Term::BishopPair,
+        Term::RookFiles,
+    ];
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Term::Material => "Material",
+            Term::PieceSquares => "Piece squares",
+            Term::Pawns => "Pawns",
+            Term::Mobility => "Mobility",
+            Term::KingSafety => "King safety",
+            Term::BishopPair => "Bishop pair",
+            Term::RookFiles => "Rook files",
+        };
+        f.pad(name)
+    }
+}
+
+// Evaluation split into its terms, for debugging and tuning
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    // Scores of each term, indexed by the term and side
+    pub terms: [[Score; 2]; Term::ALL.len()],
+    // Game phase from 24 (all pieces on the board) to 0 (pawn endgame)
+    pub phase: i32,
+    // Centipawns from the side to move's perspective
+    pub score: i32,
+}
+
+impl Trace {
+    pub fn term(&self, term: Term, side: Side) -> Score {
+        self.terms[term as usize][side as usize]
+    }
+
+    // Sum of all terms from White's perspective
+    pub fn total(&self) -> Score {
+        self.terms.iter().fold(Score::default(), |total, [white, black]| total + *white - *black)
+    }
+
+    fn add(&mut self, term: Term, side: Side, score: Score) {
+        self.terms[term as usize][side as usize] += score;
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:>13} | {:^11} | {:^11} | {:>8}", "Term", "White", "Black", "Total")?;
+        writeln!(
+            f,
+            "{:>13} | {:>5} {:>5} | {:>5} {:>5} | {:>5} {:>5}",
+            "", "MG", "EG", "MG", "EG", "MG", "EG"
+        )?;
+        for term in Term::ALL {
+            let (white, black) = (self.term(term, Side::White), self.term(term, Side::Black));
+            let total = white - black;
+            writeln!(
+                f,
+                "{:>13} | {:>5} {:>5} | {:>5} {:>5} | {:>5} {:>5}",
+                term, white.mg, white.eg, black.mg, black.eg, total.mg, total.eg
+            )?;
+        }
+
+        let total = self.total();
+        writeln!(f, "{:>13} | {:>25} | {:>5} {:>5}", "Total", "", total.mg, total.eg)?;
+        write!(f, "Phase: {}/{}  Score: {} (side to move)", self.phase, MAX_PHASE, self.score)
+    }
+}
+
+const MAX_PHASE: i32 = 24;
+
+// Contribution of each piece to the game phase, indexed like bitboards of one side
+const PHASE_WEIGHTS: [i32; 6] = [0, 4, 2, 1, 1, 0];
+
+const MATERIAL: [Score; 6] = [
+    Score::new(0, 0),
+    Score::new(1025, 936),
+    Score::new(477, 512),
+    Score::new(365, 297),
+    Score::new(337, 281),
+    Score::new(82, 94),
+];
+
+// Piece-square tables from White's point of view, with a8 as the first square
+//
+// https://www.chessprogramming.org/Simplified_Evaluation_Function
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const ROOK_EG: [i32; 64] = [
+      5,   5,   5,   5,   5,   5,   5,   5,
+     10,  10,  10,  10,  10,  10,  10,  10,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     80,  80,  80,  80,  80,  80,  80,  80,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     20,  20,  20,  20,  20,  20,  20,  20,
+     10,  10,  10,  10,  10,  10,  10,  10,
+     10,  10,  10,  10,  10,  10,  10,  10,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const PIECE_SQUARES_MG: [[i32; 64]; 6] = [KING_MG, QUEEN, ROOK_MG, BISHOP, KNIGHT, PAWN_MG];
+const PIECE_SQUARES_EG: [[i32; 64]; 6] = [KING_EG, QUEEN, ROOK_EG, BISHOP, KNIGHT, PAWN_EG];
+
+const DOUBLED_PAWN: Score = Score::new(-10, -20);
+const ISOLATED_PAWN: Score = Score::new(-10, -15);
+const BACKWARD_PAWN: Score = Score::new(-8, -10);
+// Indexed by the rank relative to the pawn's side, from its first rank
+const PASSED_PAWN: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(5, 10),
+    Score::new(10, 20),
+    Score::new(15, 35),
+    Score::new(25, 60),
+    Score::new(40, 100),
+    Score::new(60, 150),
+    Score::new(0, 0),
+];
+
+// Bonus per reachable square and the average number of squares, indexed
+// like bitboards of one side
+const MOBILITY: [(Score, i32); 6] = [
+    (Score::new(0, 0), 0),
+    (Score::new(1, 2), 13),
+    (Score::new(2, 4), 7),
+    (Score::new(5, 5), 6),
+    (Score::new(4, 4), 4),
+    (Score::new(0, 0), 0),
+];
+
+const PAWN_SHIELD: [Score; 2] = [Score::new(10, 0), Score::new(5, 0)];
+// Weights of pieces attacking the squares around the enemy king
+const KING_ATTACK_WEIGHTS: [i32; 6] = [0, 5, 3, 2, 2, 0];
+const KING_ATTACK_PENALTY: Score = Score::new(-8, 0);
+
+const BISHOP_PAIR: Score = Score::new(30, 50);
+const ROOK_OPEN_FILE: Score = Score::new(25, 10);
+const ROOK_SEMI_OPEN_FILE: Score = Score::new(12, 5);
+
+// Evaluates the position in centipawns from the side to move's perspective
+//
+// https://www.chessprogramming.org/Evaluation
+pub fn evaluate(position: &Position) -> i32 {
+    trace(position).score
+}
+
+// Evaluates the position, keeping the score of each term
+pub fn trace(position: &Position) -> Trace {
+    let mut trace = Trace::default();
+
+    for side in [Side::White, Side::Black] {
+        evaluate_material(position, side, &mut trace);
+        evaluate_pawns(position, side, &mut trace);
+        evaluate_pieces(position, side, &mut trace);
+        evaluate_king(position, side, &mut trace);
+    }
+
+    trace.phase = (0..6)
+        .map(|piece| {
+            let count = count_bits(position.bitboards[piece] | position.bitboards[piece + 6]);
+            PHASE_WEIGHTS[piece] * count as i32
+        })
+        .sum::<i32>()
+        .min(MAX_PHASE);
+
+    let score = trace.total().taper(trace.phase);
+    trace.score = match position.side_to_move {
+        Side::White => score,
+        Side::Black => -score,
+    };
+    trace
+}
+
+// Returns the square as seen from White's side, for the piece-square tables
+fn relative_square(side: Side, square: u8) -> usize {
+    match side {
+        Side::White => square as usize,
+        Side::Black => square as usize ^ 56,
+    }
+}
+
+// Returns the rank of the square counted from the side's first rank (0 to 7)
+fn relative_rank(side: Side, square: u8) -> usize {
+    7 - relative_square(side, square) / 8
+}
+
+fn file_mask(file: u8) -> u64 {
+    A_FILE << file
+}
+
+fn adjacent_files(file: u8) -> u64 {
+    let left = if file > 0 { file_mask(file - 1) } else { 0 };
+    let right = if file < 7 { file_mask(file + 1) } else { 0 };
+    left | right
+}
+
+// Returns the rows a pawn on the square still has to pass
+fn rows_ahead(side: Side, square: u8) -> u64 {
+    let (row, _) = coords(square);
+    match side {
+        Side::White => (1 << (row * 8)) - 1,
+        Side::Black => u64::MAX.checked_shl((row as u32 + 1) * 8).unwrap_or(0),
+    }
+}
+
+fn evaluate_material(position: &Position, side: Side, trace: &mut Trace) {
+    for piece in Bitboard::WHITE_KING..=Bitboard::WHITE_PAWNS {
+        let mut bitboard = position.bitboards[piece + side.piece_offset()];
+        trace.add(Term::Material, side, MATERIAL[piece] * count_bits(bitboard) as i32);
+
+        while bitboard != 0 {
+            let square = relative_square(side, pop_ls1b(&mut bitboard));
+            let score =
+                Score::new(PIECE_SQUARES_MG[piece][square], PIECE_SQUARES_EG[piece][square]);
+            trace.add(Term::PieceSquares, side, score);
+        }
+    }
+}
+
+// Doubled, isolated, backward and passed pawns
+//
+// https://www.chessprogramming.org/Pawn_Structure
+fn evaluate_pawns(position: &Position, side: Side, trace: &mut Trace) {
+    let pawns = position.bitboards[Bitboard::WHITE_PAWNS + side.piece_offset()];
+    let enemy_pawns = position.bitboards[Bitboard::WHITE_PAWNS + (!side).piece_offset()];
+
+    for file in 0..8 {
+        let count = count_bits(pawns & file_mask(file)) as i32;
+        if count > 1 {
+            trace.add(Term::Pawns, side, DOUBLED_PAWN * (count - 1));
+        }
+    }
+
+    let mut bitboard = pawns;
+    while bitboard != 0 {
+        let square = pop_ls1b(&mut bitboard);
+        let file = coords(square).1;
+        let ahead = rows_ahead(side, square);
+
+        if enemy_pawns & ahead & (file_mask(file) | adjacent_files(file)) == 0 {
+            trace.add(Term::Pawns, side, PASSED_PAWN[relative_rank(side, square)]);
+        }
+
+        if pawns & adjacent_files(file) == 0 {
+            trace.add(Term::Pawns, side, ISOLATED_PAWN);
+        } else if pawns & adjacent_files(file) & !ahead == 0 {
+            // No pawns on the adjacent files can support it, and it cannot
+            // advance safely
+            let stop = match side {
+                Side::White => square - 8,
+                Side::Black => square + 8,
+            };
+            if pawn_attacks(side, stop) & enemy_pawns != 0 {
+                trace.add(Term::Pawns, side, BACKWARD_PAWN);
+            }
+        }
+    }
+}
+
+// Mobility, attacks on the enemy king, bishop pair and rooks on open files
+fn evaluate_pieces(position: &Position, side: Side, trace: &mut Trace) {
+    let bitboards = &position.bitboards;
+    let occupancy = bitboards[Bitboard::ALL_PIECES];
+    let own_pawns = bitboards[Bitboard::WHITE_PAWNS + side.piece_offset()];
+    let enemy_pawns = bitboards[Bitboard::WHITE_PAWNS + (!side).piece_offset()];
+
+    // Squares attacked by enemy pawns do not count into mobility
+    let mut pawn_attacked = 0;
+    let mut pawns = enemy_pawns;
+    while pawns != 0 {
+        pawn_attacked |= pawn_attacks(!side, pop_ls1b(&mut pawns));
+    }
+    let area = !(bitboards[side.pieces_index()] | pawn_attacked);
+
+    let enemy_king = bitboards[Bitboard::WHITE_KING + (!side).piece_offset()];
+    let king_zone = if enemy_king != 0 {
+        king_attacks(enemy_king.trailing_zeros() as u8) | enemy_king
+    } else {
+        0
+    };
+    let (mut attackers, mut attack_weight) = (0, 0);
+
+    for piece in Bitboard::WHITE_QUEENS..=Bitboard::WHITE_KNIGHTS {
+        let mut bitboard = bitboards[piece + side.piece_offset()];
+        while bitboard != 0 {
+            let square = pop_ls1b(&mut bitboard);
+            let attacks = match piece {
+                Bitboard::WHITE_QUEENS => queen_attacks(square, occupancy),
+                Bitboard::WHITE_ROOKS => rook_attacks(square, occupancy),
+                Bitboard::WHITE_BISHOPS => bishop_attacks(square, occupancy),
+                _ => knight_attacks(square),
+            };
+
+            let (bonus, average) = MOBILITY[piece];
+            trace.add(Term::Mobility, side, bonus * (count_bits(attacks & area) as i32 - average));
+
+            if attacks & king_zone != 0 {
+                attackers += 1;
+                attack_weight += KING_ATTACK_WEIGHTS[piece];
+            }
+
+            if piece == Bitboard::WHITE_ROOKS {
+                let file = file_mask(coords(square).1);
+                if (own_pawns | enemy_pawns) & file == 0 {
+                    trace.add(Term::RookFiles, side, ROOK_OPEN_FILE);
+                } else if own_pawns & file == 0 {
+                    trace.add(Term::RookFiles, side, ROOK_SEMI_OPEN_FILE);
+                }
+            }
+        }
+    }
+
+    // A single attacker is rarely dangerous, the penalty goes to the defender
+    if attackers >= 2 {
+        trace.add(Term::KingSafety, !side, KING_ATTACK_PENALTY * attack_weight);
+    }
+
+    if count_bits(bitboards[Bitboard::WHITE_BISHOPS + side.piece_offset()]) >= 2 {
+        trace.add(Term::BishopPair, side, BISHOP_PAIR);
+    }
+}
+
+// Pawn shield in front of the king
+fn evaluate_king(position: &Position, side: Side, trace: &mut Trace) {
+    let king = position.bitboards[Bitboard::WHITE_KING + side.piece_offset()];
+    if king == 0 {
+        return;
+    }
+
+    let square = king.trailing_zeros() as u8;
+    let pawns = position.bitboards[Bitboard::WHITE_PAWNS + side.piece_offset()];
+    let files = file_mask(coords(square).1) | adjacent_files(coords(square).1);
+    let (row, _) = coords(square);
+
+    for (distance, &bonus) in (1..).zip(PAWN_SHIELD.iter()) {
+        let shield_row = match side {
+            Side::White => row.checked_sub(distance),
+            Side::Black => Some(row + distance).filter(|&row| row < 8),
+        };
+        if let Some(shield_row) = shield_row {
+            let shield = pawns & files & (0xFF << (shield_row * 8));
+            trace.add(Term::KingSafety, side, bonus * count_bits(shield) as i32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::STARTING_FEN;
+
+    fn trace_fen(fen: &str) -> Trace {
+        trace(&Position::from_fen(fen).unwrap())
+    }
+
+    // Flips the board vertically and swaps the colors of the pieces
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let swap_case =
+            |text: &str| -> String {
+                text.chars()
+                    .map(|c| {
+                        if c.is_uppercase() {
+                            c.to_ascii_lowercase()
+                        } else {
+                            c.to_ascii_uppercase()
+                        }
+                    })
+                    .collect()
+            };
+
+        let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        let en_passant = match fields[3] {
+            "-" => "-".to_string(),
+            square => format!("{}{}", &square[..1], 9 - square[1..].parse::<u8>().unwrap()),
+        };
+
+        let castling: String = swap_case(fields[2]);
+        format!("{} {} {} {} 0 1", placement.join("/"), side, castling, en_passant)
+    }
+
+    #[test]
+    fn evaluates_start_position_as_equal() {
+        let trace = trace_fen(STARTING_FEN);
+
+        assert_eq!(trace.score, 0);
+        assert_eq!(trace.phase, 24);
+        assert_eq!(trace.total(), Score::default());
+    }
+
+    #[test]
+    fn is_symmetric() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        ];
+
+        for fen in fens {
+            let (trace, mirrored) = (trace_fen(fen), trace_fen(&mirror(fen)));
+            assert_eq!(trace.score, mirrored.score, "{}", fen);
+            assert_eq!(trace.total(), Score::default() - mirrored.total(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn reports_score_from_side_to_move() {
+        let white = trace_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1");
+        let black = trace_fen("4k3/8/8/8/8/8/8/Q3K3 b - - 0 1");
+
+        assert!(white.score > 800);
+        assert_eq!(white.score, -black.score);
+        assert_eq!(white.phase, 4);
+    }
+
+    #[test]
+    fn tapers_by_phase() {
+        let score = Score::new(100, 20);
+
+        assert_eq!(score.taper(MAX_PHASE), 100);
+        assert_eq!(score.taper(0), 20);
+        assert_eq!(score.taper(MAX_PHASE / 2), 60);
+    }
+
+    #[test]
+    fn evaluates_pawn_structure() {
+        // Doubled and isolated pawns on the a file
+        let trace = trace_fen("4k3/8/8/8/8/P7/P7/4K3 w - - 0 1");
+        let passed = PASSED_PAWN[1] + PASSED_PAWN[2];
+        assert_eq!(trace.term(Term::Pawns, Side::White), DOUBLED_PAWN + ISOLATED_PAWN * 2 + passed);
+
+        // Passed pawn on the seventh rank, blocked passer on the fourth
+        let trace = trace_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(trace.term(Term::Pawns, Side::White), PASSED_PAWN[6] + ISOLATED_PAWN);
+
+        // Backward pawn on d3 which cannot advance past the c5 pawn
+        let trace = trace_fen("4k3/8/8/2p5/4P3/3P4/8/4K3 w - - 0 1");
+        assert_eq!(trace.term(Term::Pawns, Side::White), BACKWARD_PAWN + PASSED_PAWN[3]);
+    }
+
+    #[test]
+    fn evaluates_piece_terms() {
+        let trace = trace_fen("4k3/pppppppp/8/8/8/8/1PPPPPPP/R1B1KB2 w - - 0 1");
+        assert_eq!(trace.term(Term::BishopPair, Side::White), BISHOP_PAIR);
+        assert_eq!(trace.term(Term::BishopPair, Side::Black), Score::default());
+        assert_eq!(trace.term(Term::RookFiles, Side::White), ROOK_SEMI_OPEN_FILE);
+
+        let trace = trace_fen("4k3/1ppppppp/8/8/8/8/1PPPPPPP/R3K3 w - - 0 1");
+        assert_eq!(trace.term(Term::RookFiles, Side::White), ROOK_OPEN_FILE);
+
+        let cramped = trace_fen("4k3/8/8/8/8/1P6/PP6/NK6 w - - 0 1");
+        let central = trace_fen("4k3/8/8/8/3N4/1P6/PP6/1K6 w - - 0 1");
+        assert!(
+            cramped.term(Term::Mobility, Side::White).mg
+                < central.term(Term::Mobility, Side::White).mg
+        );
+    }
+
+    #[test]
+    fn evaluates_king_safety() {
+        let sheltered = trace_fen("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1");
+        assert_eq!(sheltered.term(Term::KingSafety, Side::White), PAWN_SHIELD[0] * 3);
+
+        let attacked = trace_fen("4k3/8/8/8/8/5n1q/5PPP/6K1 w - - 0 1");
+        let penalty = KING_ATTACK_PENALTY * (KING_ATTACK_WEIGHTS[1] + KING_ATTACK_WEIGHTS[4]);
+        assert_eq!(attacked.term(Term::KingSafety, Side::White), PAWN_SHIELD[0] * 3 + penalty);
+    }
+
+    #[test]
+    fn prints_trace() {
+        let output = trace_fen(STARTING_FEN).to_string();
+
+        assert!(output.contains("Bishop pair |    30    50 |    30    50 |     0     0"));
+        assert!(output.ends_with("Phase: 24/24  Score: 0 (side to move)"));
+    }
+}
@@ -1,65 +1,235 @@
 use crate::{
     bitboard::*,
-    constants::HUMAN_SQUARES,
-    defs::{Bitboard, Castling, CastlingRights, Side, Square},
+    constants::{HUMAN_SQUARES, PIECE_CHARS},
+    defs::{Bitboard, CastlingRights, Side, Square},
+    movegen::{CASTLING_MOVES, STANDARD_CASTLING_ROOKS},
     position::Position,
 };
+use std::{error, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenError {
+    // FEN has to consist of 4 or 6 whitespace separated fields
+    FieldCount(usize),
+    RankCount(usize),
+    // Rank is given as on the board (8 to 1)
+    BadRankLength { rank: u8, length: u8 },
+    // Offset is a character index in the piece placement field
+    UnknownPiece { piece: char, offset: usize },
+    BadSide(String),
+    BadCastling(String),
+    BadEnPassant(String),
+    BadClock(String),
+    MissingKing(Side),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::FieldCount(count) => write!(f, "expected 4 or 6 fields, found {}", count),
+            FenError::RankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::BadRankLength { rank, length } => {
+                write!(f, "rank {} has {} squares instead of 8", rank, length)
+            }
+            FenError::UnknownPiece { piece, offset } => {
+                write!(f, "unknown piece '{}' at offset {}", piece, offset)
+            }
+            FenError::BadSide(side) => write!(f, "invalid side to move \"{}\"", side),
+            FenError::BadCastling(rights) => write!(f, "invalid castling rights \"{}\"", rights),
+            FenError::BadEnPassant(square) => {
+                write!(f, "invalid en passant square \"{}\"", square)
+            }
+            FenError::BadClock(clock) => write!(f, "invalid move clock \"{}\"", clock),
+            FenError::MissingKing(side) => write!(f, "{} king is missing", side),
+        }
+    }
+}
+
+impl error::Error for FenError {}
 
 impl Position {
     // Creates a position from [FEN] string
-    // TODO: Return `Err` on invalid FEN strings
     //
     // [FEN]: https://www.chessprogramming.org/Forsyth-Edwards_Notation
-    pub fn from_fen(fen: &str) -> Self {
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
         let fen_parts: Vec<&str> = fen.split_whitespace().collect();
-        assert!(fen_parts.len() == 4 || fen_parts.len() == 6);
-
-        Self {
-            bitboards: place_pieces(fen_parts[0]),
-            en_passant_square: get_square_id(fen_parts[3]),
-            castling_rights: get_castling_rights(fen_parts[2]),
-            side_to_move: fen_parts[1].into(),
-            fifty_move_count: fen_parts.get(4).unwrap_or(&"0").parse().unwrap(),
-            halfmove_count: get_halfmoves(
-                fen_parts.get(5).unwrap_or(&"1").parse().unwrap(),
-                fen_parts[1].into(),
-            ),
+        if fen_parts.len() != 4 && fen_parts.len() != 6 {
+            return Err(FenError::FieldCount(fen_parts.len()));
+        }
+
+        let bitboards = place_pieces(fen_parts[0])?;
+        if bitboards[Bitboard::WHITE_KING] == 0 {
+            return Err(FenError::MissingKing(Side::White));
+        }
+        if bitboards[Bitboard::BLACK_KING] == 0 {
+            return Err(FenError::MissingKing(Side::Black));
+        }
+
+        let side_to_move = get_side(fen_parts[1])?;
+        let (castling_rights, castling_rooks, chess960) =
+            get_castling_rights(fen_parts[2], &bitboards)?;
+
+        Ok(Self {
+            bitboards,
+            en_passant_square: get_en_passant_square(fen_parts[3])?,
+            castling_rights,
+            castling_rooks,
+            chess960,
+            side_to_move,
+            fifty_move_count: get_clock(fen_parts.get(4).unwrap_or(&"0"))?,
+            halfmove_count: get_halfmoves(fen_parts.get(5).unwrap_or(&"1"), side_to_move)?,
+            key: 0,
+        }
+        .with_key())
+    }
+
+    // Serializes the position to a canonical FEN string, using X-FEN rook
+    // files for castling rights which "KQkq" cannot tell apart in Chess960
+    pub fn to_fen(&self) -> String {
+        self.format_fen(false)
+    }
+
+    // Serializes the position to a FEN string with Shredder-FEN castling
+    // rights, which are the files of the castling rooks (like "HAha")
+    pub fn to_shredder_fen(&self) -> String {
+        self.format_fen(true)
+    }
+
+    fn format_fen(&self, shredder: bool) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            get_placement(self),
+            if self.side_to_move == Side::White { "w" } else { "b" },
+            castling_rights_to_str(self, shredder),
+            HUMAN_SQUARES.get(self.en_passant_square as usize).unwrap_or(&"-"),
+            self.fifty_move_count,
+            self.halfmove_count / 2
+        )
+    }
+}
+
+// Creates the piece placement part of a FEN string
+fn get_placement(position: &Position) -> String {
+    let mut placement = String::new();
+
+    for rank in 0..8 {
+        let mut empty = 0;
+
+        for file in 0..8 {
+            match position.piece_at(sq(rank, file)) {
+                Some(piece) => {
+                    if empty > 0 {
+                        placement.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    placement.push(PIECE_CHARS[piece]);
+                }
+                None => empty += 1,
+            }
+        }
+
+        if empty > 0 {
+            placement.push_str(&empty.to_string());
+        }
+        if rank < 7 {
+            placement.push('/');
         }
     }
+
+    placement
+}
+
+// Creates FEN castling rights string (like "KQkq") from the castling rights,
+// with files of the rooks for Shredder-FEN, or for X-FEN when there is
+// another rook of the side further out on the same wing
+fn castling_rights_to_str(position: &Position, shredder: bool) -> String {
+    let mut rights_str = String::new();
+
+    for (index, castling) in CASTLING_MOVES.iter().enumerate() {
+        if position.castling_rights & castling.right == 0 {
+            continue;
+        }
+
+        let (rank, rooks) = back_rank_rooks(&position.bitboards, castling.side);
+        let file = coords(position.castling_rooks[index]).1;
+        let mut outer_files = if index % 2 == 0 { file + 1..8 } else { 0..file };
+
+        let c = if !shredder && !outer_files.any(|outer| get_bit(rooks, sq(rank, outer)) != 0) {
+            if index % 2 == 0 {
+                'k'
+            } else {
+                'q'
+            }
+        } else {
+            (b'a' + file) as char
+        };
+        rights_str.push(match castling.side {
+            Side::White => c.to_ascii_uppercase(),
+            Side::Black => c,
+        });
+    }
+
+    if rights_str.is_empty() {
+        "-".to_string()
+    } else {
+        rights_str
+    }
 }
 
 // Takes piece placement part of a FEN string, returns an array of bitboards
 // representing the placement
-fn place_pieces(placement: &str) -> [u64; 15] {
+fn place_pieces(placement: &str) -> Result<[u64; 15], FenError> {
     let mut bitboards = [0u64; 15];
-    let (mut rank, mut file) = (0u8, 0u8);
 
-    for c in placement.chars() {
-        let mut set_piece = |board_index: usize| {
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::RankCount(ranks.len()));
+    }
+
+    let mut offset = 0;
+    for (rank, rank_str) in ranks.iter().enumerate() {
+        let rank = rank as u8;
+        let mut file = 0u8;
+
+        for c in rank_str.chars() {
+            let board_index = match c {
+                'K' => Bitboard::WHITE_KING,
+                'Q' => Bitboard::WHITE_QUEENS,
+                'R' => Bitboard::WHITE_ROOKS,
+                'B' => Bitboard::WHITE_BISHOPS,
+                'N' => Bitboard::WHITE_KNIGHTS,
+                'P' => Bitboard::WHITE_PAWNS,
+                'k' => Bitboard::BLACK_KING,
+                'q' => Bitboard::BLACK_QUEENS,
+                'r' => Bitboard::BLACK_ROOKS,
+                'b' => Bitboard::BLACK_BISHOPS,
+                'n' => Bitboard::BLACK_KNIGHTS,
+                'p' => Bitboard::BLACK_PAWNS,
+                '1'..='8' => {
+                    file += c as u8 - 0x30;
+                    offset += 1;
+                    // Stop early, so that overlong ranks cannot overflow the file
+                    if file > 8 {
+                        return Err(FenError::BadRankLength { rank: 8 - rank, length: file });
+                    }
+                    continue;
+                }
+                _ => return Err(FenError::UnknownPiece { piece: c, offset }),
+            };
+
+            if file >= 8 {
+                return Err(FenError::BadRankLength { rank: 8 - rank, length: file + 1 });
+            }
             set_bit(&mut bitboards[board_index], sq(rank, file));
             file += 1;
-        };
+            offset += 1;
+        }
 
-        match c {
-            'K' => set_piece(Bitboard::WHITE_KING),
-            'Q' => set_piece(Bitboard::WHITE_QUEENS),
-            'R' => set_piece(Bitboard::WHITE_ROOKS),
-            'B' => set_piece(Bitboard::WHITE_BISHOPS),
-            'N' => set_piece(Bitboard::WHITE_KNIGHTS),
-            'P' => set_piece(Bitboard::WHITE_PAWNS),
-            'k' => set_piece(Bitboard::BLACK_KING),
-            'q' => set_piece(Bitboard::BLACK_QUEENS),
-            'r' => set_piece(Bitboard::BLACK_ROOKS),
-            'b' => set_piece(Bitboard::BLACK_BISHOPS),
-            'n' => set_piece(Bitboard::BLACK_KNIGHTS),
-            'p' => set_piece(Bitboard::BLACK_PAWNS),
-            '1'..='8' => file += c as u8 - 0x30,
-            '/' => {
-                rank += 1;
-                file = 0;
-            }
-            _ => (),
+        if file != 8 {
+            return Err(FenError::BadRankLength { rank: 8 - rank, length: file });
         }
+        // Skip the rank separator
+        offset += 1;
     }
 
     bitboards[Bitboard::WHITE_PIECES] = sum_bitboards(&bitboards[0..6]);
@@ -67,29 +237,88 @@ fn place_pieces(placement: &str) -> [u64; 15] {
     bitboards[Bitboard::ALL_PIECES] =
         bitboards[Bitboard::WHITE_PIECES] | bitboards[Bitboard::BLACK_PIECES];
 
-    bitboards
+    Ok(bitboards)
+}
+
+fn get_side(side_str: &str) -> Result<Side, FenError> {
+    match side_str {
+        "w" | "b" => Ok(side_str.into()),
+        _ => Err(FenError::BadSide(side_str.to_string())),
+    }
 }
 
-// Parses FEN castling rights string (like "KQkq"), returns castling right integer
-fn get_castling_rights(rights_str: &str) -> CastlingRights {
+// Parses FEN castling rights string (like "KQkq"), which may also give the
+// files of the castling rooks as in Shredder-FEN and X-FEN (like "HAha").
+// "K" and "Q" stand for the outermost rook on the wing, or the corner when
+// there is none. Returns the castling right integer, the squares of the rooks
+// and whether the rights are specific to Chess960
+fn get_castling_rights(
+    rights_str: &str,
+    bitboards: &[u64; 15],
+) -> Result<(CastlingRights, [u8; 4], bool), FenError> {
     let mut rights = 0;
+    let mut rooks = STANDARD_CASTLING_ROOKS;
+    let mut chess960 = false;
+    if rights_str == "-" {
+        return Ok((rights, rooks, chess960));
+    }
 
+    let bad_castling = || FenError::BadCastling(rights_str.to_string());
     for c in rights_str.chars() {
-        match c {
-            'K' => rights |= Castling::WK,
-            'Q' => rights |= Castling::WQ,
-            'k' => rights |= Castling::BK,
-            'q' => rights |= Castling::BQ,
-            _ => break,
+        let side = if c.is_ascii_uppercase() { Side::White } else { Side::Black };
+        let (rank, side_rooks) = back_rank_rooks(bitboards, side);
+        let king = bitboards[Bitboard::WHITE_KING + side.piece_offset()] & (0xFF << (rank * 8));
+        // The king is assumed on the e-file if it is not on its back rank
+        let king_file = if king != 0 { coords(get_ls1b_index(king)).1 } else { 4 };
+        let is_rook = |file: &u8| get_bit(side_rooks, sq(rank, *file)) != 0;
+
+        let file = match c.to_ascii_lowercase() {
+            'k' => (king_file + 1..8).rev().find(is_rook).unwrap_or(7),
+            'q' => (0..king_file).find(is_rook).unwrap_or(0),
+            file @ 'a'..='h' if file as u8 - b'a' != king_file => file as u8 - b'a',
+            _ => return Err(bad_castling()),
+        };
+
+        let index = side as usize * 2 + if file > king_file { 0 } else { 1 };
+        let right = CASTLING_MOVES[index].right;
+        if rights & right != 0 {
+            return Err(bad_castling());
         }
+        rights |= right;
+        rooks[index] = sq(rank, file);
+        chess960 |= king_file != 4 || (file != 0 && file != 7);
+    }
+
+    Ok((rights, rooks, chess960))
+}
+
+// Returns the back rank of the side and the rooks of the side standing on it
+fn back_rank_rooks(bitboards: &[u64; 15], side: Side) -> (u8, u64) {
+    let rank = match side {
+        Side::White => 7,
+        Side::Black => 0,
+    };
+
+    (rank, bitboards[Bitboard::WHITE_ROOKS + side.piece_offset()] & (0xFF << (rank * 8)))
+}
+
+// Parses FEN en passant square, which has to be on the 3rd or 6th rank
+fn get_en_passant_square(square_str: &str) -> Result<u8, FenError> {
+    let square = get_square_id(square_str);
+
+    match (square_str, coords(square).0) {
+        ("-", _) | (_, 2) | (_, 5) => Ok(square),
+        _ => Err(FenError::BadEnPassant(square_str.to_string())),
     }
+}
 
-    rights
+fn get_clock(clock_str: &str) -> Result<u16, FenError> {
+    clock_str.parse().map_err(|_| FenError::BadClock(clock_str.to_string()))
 }
 
 // Takes a human-readable square representation (like "e4")
 // and returns the square ID (0-63) or "no square" (64)
-fn get_square_id(square_str: &str) -> u8 {
+pub(crate) fn get_square_id(square_str: &str) -> u8 {
     if square_str == "-" {
         return Square::NoSquare as u8;
     };
@@ -103,34 +332,101 @@ fn get_square_id(square_str: &str) -> u8 {
 }
 
 // Returns total halfmove count in a game
-fn get_halfmoves(full_moves: u16, side_to_move: Side) -> u16 {
-    (full_moves * 2) + if side_to_move == Side::Black { 1 } else { 0 }
+// Converts the FEN fullmove number, rejecting ones which do not fit the halfmove count
+fn get_halfmoves(full_moves_str: &str, side_to_move: Side) -> Result<u16, FenError> {
+    get_clock(full_moves_str)?
+        .checked_mul(2)
+        .and_then(|halfmoves| halfmoves.checked_add(side_to_move as u16))
+        .ok_or_else(|| FenError::BadClock(full_moves_str.to_string()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::defs::Piece::*;
+    use crate::defs::{Castling, Piece::*};
     use crate::map;
     use Square::*;
 
     #[test]
     fn gets_halfmoves() {
-        assert_eq!(get_halfmoves(0, Side::White), 0);
-        assert_eq!(get_halfmoves(0, Side::Black), 1);
-        assert_eq!(get_halfmoves(10, Side::White), 20);
-        assert_eq!(get_halfmoves(10, Side::Black), 21);
+        assert_eq!(get_halfmoves("0", Side::White), Ok(0));
+        assert_eq!(get_halfmoves("0", Side::Black), Ok(1));
+        assert_eq!(get_halfmoves("10", Side::White), Ok(20));
+        assert_eq!(get_halfmoves("10", Side::Black), Ok(21));
+        assert_eq!(get_halfmoves("32767", Side::Black), Ok(65535));
+        assert_eq!(get_halfmoves("32768", Side::White), Err(FenError::BadClock("32768".into())));
+    }
+
+    // Parses castling rights for the starting placement
+    fn castling_rights(rights_str: &str) -> Result<CastlingRights, FenError> {
+        let bitboards = place_pieces(crate::constants::STARTING_FEN.split(' ').next().unwrap())?;
+        get_castling_rights(rights_str, &bitboards).map(|(rights, _, _)| rights)
     }
 
     #[test]
     fn gets_castling_rights() {
-        assert_eq!(get_castling_rights("-"), 0);
+        assert_eq!(castling_rights("-"), Ok(0));
         assert_eq!(
-            get_castling_rights("KQkq"),
-            Castling::WK | Castling::WQ | Castling::BK | Castling::BQ
+            castling_rights("KQkq"),
+            Ok(Castling::WK | Castling::WQ | Castling::BK | Castling::BQ)
         );
-        assert_eq!(get_castling_rights("Qkq"), Castling::WQ | Castling::BK | Castling::BQ);
-        assert_eq!(get_castling_rights("Kk"), Castling::WK | Castling::BK);
+        assert_eq!(castling_rights("Qkq"), Ok(Castling::WQ | Castling::BK | Castling::BQ));
+        assert_eq!(castling_rights("Kk"), Ok(Castling::WK | Castling::BK));
+        assert_eq!(castling_rights("HAha"), castling_rights("KQkq"));
+
+        // Rook files of the standard placement do not make it a Chess960 position
+        let standard = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let shredder = standard.to_shredder_fen();
+        assert_eq!(shredder.split(' ').nth(2), Some("HAha"));
+        assert_eq!(Position::from_fen(&shredder).unwrap(), standard);
+    }
+
+    #[test]
+    fn rejects_invalid_castling_rights() {
+        assert_eq!(castling_rights("KQx"), Err(FenError::BadCastling("KQx".to_string())));
+        assert_eq!(castling_rights("KK"), Err(FenError::BadCastling("KK".to_string())));
+        assert_eq!(castling_rights("KH"), Err(FenError::BadCastling("KH".to_string())));
+        assert_eq!(castling_rights("E"), Err(FenError::BadCastling("E".to_string())));
+        assert_eq!(castling_rights(""), Ok(0));
+    }
+
+    #[test]
+    fn parses_chess960_castling_rights() {
+        let shredder = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+        let position = Position::from_fen(shredder).unwrap();
+
+        assert_eq!(position.castling_rooks, [H1 as u8, F1 as u8, H8 as u8, F8 as u8]);
+        assert!(position.chess960);
+        assert_eq!(position.to_shredder_fen(), shredder);
+        // X-FEN uses "KQkq" for the outermost rooks
+        assert_eq!(position.to_fen(), shredder.replace("HFhf", "KQkq"));
+        assert_eq!(Position::from_fen(&position.to_fen()).unwrap(), position);
+
+        assert!(!Position::from_fen(crate::constants::STARTING_FEN).unwrap().chess960);
+
+        // Castling with Shredder-FEN rights in the standard placement moves the king two squares
+        let fen = "rnbqk2r/pppppppp/8/8/8/8/PPPPPPPP/RNBQK2R w HAha - 0 1";
+        let mut position = Position::from_fen(fen).unwrap();
+        assert_eq!(position, Position::from_fen(&fen.replace("HAha", "KQkq")).unwrap());
+        assert!(!position.chess960);
+        let m = position.parse_uci_move("e1g1").unwrap();
+        position.make_move(m);
+        assert_eq!(position.to_fen(), "rnbqk2r/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 b kq - 1 1");
+    }
+
+    #[test]
+    fn parses_x_fen_inner_rooks() {
+        let position = Position::from_fen("1r2k2r/8/8/8/8/8/8/RR2K2R w Bk - 0 1").unwrap();
+
+        assert_eq!(position.castling_rights, Castling::WQ | Castling::BK);
+        assert_eq!(position.castling_rooks[1], B1 as u8);
+        assert!(position.chess960);
+        assert_eq!(position.to_fen(), "1r2k2r/8/8/8/8/8/8/RR2K2R w Bk - 0 1");
+        assert_eq!(position.to_shredder_fen(), "1r2k2r/8/8/8/8/8/8/RR2K2R w Bh - 0 1");
+
+        let position = Position::from_fen("1r2k2r/8/8/8/8/8/8/RR2K2R w Qq - 0 1").unwrap();
+        assert_eq!(position.castling_rooks[1], A1 as u8);
+        assert_eq!(position.castling_rooks[3], B8 as u8);
     }
 
     #[test]
@@ -143,24 +439,28 @@ mod tests {
     }
 
     #[test]
-    fn parses_empty_fen() {
+    fn rejects_empty_fen() {
         assert_eq!(
             Position::from_fen(crate::constants::EMPTY_FEN),
-            Position {
-                bitboards: [0; 15],
-                en_passant_square: NoSquare as u8,
-                castling_rights: 0,
-                side_to_move: Side::White,
-                fifty_move_count: 0,
-                halfmove_count: 2
-            }
+            Err(FenError::MissingKing(Side::White))
+        );
+    }
+
+    #[test]
+    fn parses_fen_without_clocks() {
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - -").unwrap();
+
+        assert_eq!(
+            position.bitboards,
+            Position::from_position(map! { E1 => WhiteKing, E8 => BlackKing }).bitboards
         );
+        assert_eq!((position.fifty_move_count, position.halfmove_count), (0, 2));
     }
 
     #[test]
     fn parses_starting_fen() {
         assert_eq!(
-            Position::from_fen(crate::constants::STARTING_FEN),
+            Position::from_fen(crate::constants::STARTING_FEN).unwrap(),
             Position::from_position(map! {
                 A1 => WhiteRook, H1 => WhiteRook, A8 => BlackRook, H8 => BlackRook,
                 B1 => WhiteKnight, G1 => WhiteKnight, B8 => BlackKnight, G8 => BlackKnight,
@@ -189,20 +489,123 @@ mod tests {
 
         assert_eq!(
             Position::from_fen("1r6/3b1k2/2nq4/8/1pP1N3/8/3Q4/2B1K2R b K c3 1 27"),
-            Position {
+            Ok(Position {
                 bitboards,
                 en_passant_square: get_square_id("c3"),
                 castling_rights: Castling::WK,
+                castling_rooks: STANDARD_CASTLING_ROOKS,
+                chess960: false,
                 side_to_move: Side::Black,
                 fifty_move_count: 1,
-                halfmove_count: 55
+                halfmove_count: 55,
+                key: 0
             }
+            .with_key())
         );
     }
 
     #[test]
-    #[should_panic]
-    fn panics_on_invalid_length_fen() {
-        Position::from_fen("one two three four five");
+    fn rejects_invalid_field_count() {
+        assert_eq!(Position::from_fen("one two three four five"), Err(FenError::FieldCount(5)));
+        assert_eq!(Position::from_fen(""), Err(FenError::FieldCount(0)));
+    }
+
+    #[test]
+    fn rejects_invalid_placement() {
+        assert_eq!(Position::from_fen("4k3/8/8/8/8/8/4K3 w - - 0 1"), Err(FenError::RankCount(7)));
+        assert_eq!(
+            Position::from_fen("4k3/8/8/9/8/8/8/4K3 w - - 0 1"),
+            Err(FenError::UnknownPiece { piece: '9', offset: 8 })
+        );
+        assert_eq!(
+            Position::from_fen("4k3/8/8/8/8/8/8/4K3p w - - 0 1"),
+            Err(FenError::BadRankLength { rank: 1, length: 9 })
+        );
+        // Overlong ranks are rejected without overflowing the file counter
+        assert_eq!(
+            Position::from_fen(&format!("4k3/8/8/8/8/8/{}/4K3 w - - 0 1", "8".repeat(32))),
+            Err(FenError::BadRankLength { rank: 2, length: 16 })
+        );
+        assert_eq!(
+            Position::from_fen(&format!("4k3/8/8/8/8/8/{}/4K3 w - - 0 1", "p".repeat(300))),
+            Err(FenError::BadRankLength { rank: 2, length: 9 })
+        );
+        assert_eq!(
+            Position::from_fen("4k3/8/8/8/8/8/7/4K3 w - - 0 1"),
+            Err(FenError::BadRankLength { rank: 2, length: 7 })
+        );
+        assert_eq!(
+            Position::from_fen("4k3/8/8/8/8/8/8/4Q3 w - - 0 1"),
+            Err(FenError::MissingKing(Side::White))
+        );
+        assert_eq!(
+            Position::from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Err(FenError::MissingKing(Side::Black))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        let fen = |fields: &str| Position::from_fen(&format!("4k3/8/8/8/8/8/8/4K3 {}", fields));
+
+        assert_eq!(fen("x - - 0 1"), Err(FenError::BadSide("x".to_string())));
+        assert_eq!(fen("w KQkq- - 0 1"), Err(FenError::BadCastling("KQkq-".to_string())));
+        assert_eq!(fen("w - e4 0 1"), Err(FenError::BadEnPassant("e4".to_string())));
+        assert_eq!(fen("w - z9 0 1"), Err(FenError::BadEnPassant("z9".to_string())));
+        assert_eq!(fen("b - e3 0 1").map(|p| p.en_passant_square), Ok(E3 as u8));
+        assert_eq!(fen("w - - x 1"), Err(FenError::BadClock("x".to_string())));
+        assert_eq!(fen("w - - 0 -1"), Err(FenError::BadClock("-1".to_string())));
+        assert_eq!(fen("w - - 0 40000"), Err(FenError::BadClock("40000".to_string())));
+    }
+
+    #[test]
+    fn serializes_fen() {
+        for fen in &[
+            crate::constants::STARTING_FEN,
+            "1r6/3b1k2/2nq4/8/1pP1N3/8/3Q4/2B1K2R b K c3 1 27",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w Qk - 99 120",
+        ] {
+            assert_eq!(Position::from_fen(fen).unwrap().to_fen(), *fen);
+        }
+    }
+
+    #[test]
+    fn serializes_fen_canonically() {
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/4K3 b qK -").unwrap();
+        assert_eq!(position.to_fen(), "4k3/8/8/8/8/8/8/4K3 b Kq - 0 1");
+    }
+
+    #[test]
+    fn round_trips_random_positions() {
+        let mut random = crate::random::Random::new(0xF3A);
+        let starts = [
+            crate::constants::STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ];
+
+        for game in 0..150 {
+            let mut position = Position::from_fen(starts[game % starts.len()]).unwrap();
+
+            for _ in 0..60 {
+                let moves = position.legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+                position.make_move(moves[random.next_u64() as usize % moves.len()]);
+
+                assert_eq!(Position::from_fen(&position.to_fen()).unwrap(), position);
+            }
+        }
+    }
+
+    #[test]
+    fn formats_errors() {
+        assert_eq!(
+            FenError::UnknownPiece { piece: 'x', offset: 3 }.to_string(),
+            "unknown piece 'x' at offset 3"
+        );
+        assert_eq!(FenError::MissingKing(Side::Black).to_string(), "Black king is missing");
     }
 }
@@ -1,11 +1,299 @@
+use crate::bitboard::count_bits;
+use crate::constants::{DARK_SQUARES, LIGHT_SQUARES, STARTING_FEN};
+use crate::defs::{Bitboard, Side};
+use crate::makemove::Undo;
+use crate::moves::{Move, MoveParseError};
 use crate::position::Position;
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Checkmate { winner: Side },
+    Stalemate,
+    // Draws which end the game without a claim
+    FivefoldRepetition,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+    // Draws which can be claimed by either player
+    ThreefoldRepetition,
+    FiftyMoveRule,
+}
+
+impl Outcome {
+    pub fn winner(&self) -> Option<Side> {
+        match self {
+            Outcome::Checkmate { winner } => Some(*winner),
+            _ => None,
+        }
+    }
+}
+
+// A game played from a start position, keeping the moves for undo and redo
+#[derive(Debug, Clone)]
 pub struct Game {
-    pub position: Position,
+    position: Position,
+    start: Position,
+    // Played moves, followed by the moves which were taken back
+    moves: Vec<Move>,
+    // State needed to take back each of the played moves
+    history: Vec<Undo>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(Position::from_fen(STARTING_FEN).unwrap())
+    }
 }
 
 impl Game {
-    pub fn default() -> Self {
-        Self { position: Position::default() }
+    pub fn new(position: Position) -> Self {
+        Self { start: position.clone(), position, moves: Vec::new(), history: Vec::new() }
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    pub fn start_position(&self) -> &Position {
+        &self.start
+    }
+
+    // Moves played to reach the current position
+    pub fn moves(&self) -> &[Move] {
+        &self.moves[..self.history.len()]
+    }
+
+    // Number of moves played to reach the current position
+    pub fn ply(&self) -> usize {
+        self.history.len()
+    }
+
+    // Plays a legal move, dropping the moves which could be redone
+    pub fn play(&mut self, m: Move) -> Result<(), MoveParseError> {
+        if !self.position.legal_moves().contains(&m) {
+            return Err(MoveParseError::IllegalMove(m.to_uci()));
+        }
+
+        self.moves.truncate(self.history.len());
+        self.moves.push(m);
+        self.history.push(self.position.make_move(m));
+        Ok(())
+    }
+
+    // Takes back the last played move, keeping it for redo
+    pub fn undo(&mut self) -> Option<Move> {
+        let undo = self.history.pop()?;
+        let m = self.moves[self.history.len()];
+        self.position.unmake_move(m, undo);
+        Some(m)
+    }
+
+    // Plays again the last move taken back
+    pub fn redo(&mut self) -> Option<Move> {
+        let m = *self.moves.get(self.history.len())?;
+        self.history.push(self.position.make_move(m));
+        Some(m)
+    }
+
+    // Moves back or forward to the position after the given number of moves,
+    // returns false if there are not enough moves
+    pub fn goto(&mut self, ply: usize) -> bool {
+        if ply > self.moves.len() {
+            return false;
+        }
+
+        while self.history.len() > ply {
+            self.undo();
+        }
+        while self.history.len() < ply {
+            self.redo();
+        }
+        true
+    }
+
+    // Number of times the current position occurred in the game, counting
+    // only positions since the last capture or pawn move
+    pub fn repetitions(&self) -> usize {
+        let reversible = self.position.fifty_move_count as usize;
+        let earlier = self.history.iter().rev().take(reversible);
+
+        1 + earlier.filter(|undo| undo.key == self.position.key).count()
+    }
+
+    // Returns how the game ended in the current position, or could be ended
+    // by a draw claim, with automatic endings taking precedence
+    //
+    // https://www.chessprogramming.org/Draw
+    pub fn outcome(&self) -> Option<Outcome> {
+        let position = &self.position;
+
+        if position.legal_moves().is_empty() {
+            return Some(match position.in_check() {
+                true => Outcome::Checkmate { winner: !position.side_to_move },
+                false => Outcome::Stalemate,
+            });
+        }
+
+        let repetitions = self.repetitions();
+        if repetitions >= 5 {
+            Some(Outcome::FivefoldRepetition)
+        } else if position.fifty_move_count >= 150 {
+            Some(Outcome::SeventyFiveMoveRule)
+        } else if position.has_insufficient_material() {
+            Some(Outcome::InsufficientMaterial)
+        } else if repetitions >= 3 {
+            Some(Outcome::ThreefoldRepetition)
+        } else if position.fifty_move_count >= 100 {
+            Some(Outcome::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+}
+
+impl Position {
+    // Checks whether no sequence of legal moves can lead to a checkmate:
+    // only kings and either a single minor piece or bishops all on one color
+    pub fn has_insufficient_material(&self) -> bool {
+        let bitboards = &self.bitboards;
+        let heavy_or_pawns = [
+            Bitboard::WHITE_QUEENS,
+            Bitboard::WHITE_ROOKS,
+            Bitboard::WHITE_PAWNS,
+            Bitboard::BLACK_QUEENS,
+            Bitboard::BLACK_ROOKS,
+            Bitboard::BLACK_PAWNS,
+        ];
+        if heavy_or_pawns.iter().any(|&piece| bitboards[piece] != 0) {
+            return false;
+        }
+
+        let knights = bitboards[Bitboard::WHITE_KNIGHTS] | bitboards[Bitboard::BLACK_KNIGHTS];
+        let bishops = bitboards[Bitboard::WHITE_BISHOPS] | bitboards[Bitboard::BLACK_BISHOPS];
+
+        count_bits(knights | bishops) <= 1
+            || (knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & DARK_SQUARES == 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play_uci(game: &mut Game, moves: &str) {
+        for move_str in moves.split_whitespace() {
+            let m = game.position().parse_uci_move(move_str).unwrap();
+            game.play(m).unwrap();
+        }
+    }
+
+    fn game_from_fen(fen: &str) -> Game {
+        Game::new(Position::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn undoes_and_redoes_moves() {
+        let mut game = Game::default();
+        play_uci(&mut game, "e2e4 e7e5 g1f3");
+        let after_nf3 = game.position().clone();
+
+        assert_eq!(game.undo().map(|m| m.to_uci()), Some("g1f3".to_string()));
+        assert_eq!(game.ply(), 2);
+        assert_eq!(game.redo().map(|m| m.to_uci()), Some("g1f3".to_string()));
+        assert_eq!(game.position(), &after_nf3);
+        assert_eq!(game.redo(), None);
+
+        assert!(game.goto(0));
+        assert_eq!(game.position(), game.start_position());
+        assert_eq!(game.undo(), None);
+        assert!(game.goto(3));
+        assert_eq!(game.position(), &after_nf3);
+        assert!(!game.goto(4));
+
+        game.goto(1);
+        play_uci(&mut game, "c7c5");
+        assert_eq!(game.moves().len(), 2);
+        assert_eq!(game.redo(), None);
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        let mut game = Game::default();
+        let m = Move::new(52, 36, Bitboard::WHITE_KING, None, 0);
+
+        assert_eq!(game.play(m), Err(MoveParseError::IllegalMove("e2e4".to_string())));
+        assert_eq!(game.ply(), 0);
+    }
+
+    #[test]
+    fn detects_mates() {
+        let mut game = Game::default();
+        play_uci(&mut game, "f2f3 e7e5 g2g4 d8h4");
+        assert_eq!(game.outcome(), Some(Outcome::Checkmate { winner: Side::Black }));
+        assert_eq!(game.outcome().unwrap().winner(), Some(Side::Black));
+
+        let game = game_from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(game.outcome(), Some(Outcome::Stalemate));
+    }
+
+    #[test]
+    fn detects_repetitions() {
+        let mut game = Game::default();
+        play_uci(&mut game, "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1");
+        assert_eq!(game.outcome(), None);
+
+        play_uci(&mut game, "f6g8");
+        assert_eq!(game.repetitions(), 3);
+        assert_eq!(game.outcome(), Some(Outcome::ThreefoldRepetition));
+
+        play_uci(&mut game, "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8");
+        assert_eq!(game.repetitions(), 5);
+        assert_eq!(game.outcome(), Some(Outcome::FivefoldRepetition));
+
+        game.goto(4);
+        assert_eq!(game.outcome(), None);
+    }
+
+    #[test]
+    fn detects_move_rules() {
+        let mut game = game_from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80");
+        assert_eq!(game.outcome(), None);
+        play_uci(&mut game, "a1a2");
+        assert_eq!(game.outcome(), Some(Outcome::FiftyMoveRule));
+
+        let game = game_from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 150 80");
+        assert_eq!(game.outcome(), Some(Outcome::SeventyFiveMoveRule));
+
+        // Checkmate on the last move takes precedence over the move rules
+        let game = game_from_fen("R3k3/8/4K3/8/8/8/8/8 b - - 150 80");
+        assert_eq!(game.outcome(), Some(Outcome::Checkmate { winner: Side::White }));
+    }
+
+    #[test]
+    fn detects_insufficient_material() {
+        let insufficient = [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+            "4kb2/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+        ];
+        let sufficient = [
+            "4k3/8/8/8/8/8/8/3NKN2 w - - 0 1",
+            "4kb2/8/8/8/8/8/8/3BK3 w - - 0 1",
+            "4kn2/8/8/8/8/8/8/3BK3 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+        ];
+
+        for fen in insufficient {
+            assert_eq!(
+                game_from_fen(fen).outcome(),
+                Some(Outcome::InsufficientMaterial),
+                "{}",
+                fen
+            );
+        }
+        for fen in sufficient {
+            assert_eq!(game_from_fen(fen).outcome(), None, "{}", fen);
+        }
     }
 }
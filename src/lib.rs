@@ -1,14 +1,32 @@
 #![feature(stmt_expr_attributes)]
 
+pub mod attacks;
 pub mod bitboard;
+pub mod chess960;
 mod cli;
 pub mod constants;
 pub mod defs;
-mod fen;
+pub mod eval;
+pub mod fen;
 pub mod game;
 mod macros;
+pub mod magic;
+pub mod makemove;
+pub mod movegen;
+pub mod moves;
+pub mod perft;
+pub mod pgn;
 pub mod pieces;
 pub mod position;
+mod random;
+pub mod san;
+pub mod search;
+pub mod see;
+pub mod time;
+pub mod tt;
+pub mod uci;
+pub mod validate;
+mod zobrist;
 
 #[cfg(test)]
 mod test;
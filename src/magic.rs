@@ -0,0 +1,173 @@
+use crate::bitboard::*;
+use crate::pieces::{bishop::*, rook::*};
+use crate::random::Random;
+
+// Magic numbers found with `find_magic_number`, seeded with `MAGIC_SEED`
+pub const MAGIC_SEED: u64 = 0x5EED_CAFE;
+
+#[rustfmt::skip]
+pub const ROOK_MAGICS: [u64; 64] = [
+  0xa080012284c00010, 0x90c0002000401002, 0x0200088020420410, 0x02000420100a0040,
+  0x0100080004110002, 0x1a00210802003014, 0x1080020000800100, 0x2a80010000205880,
+  0x0000802040008000, 0x0100400020100040, 0x1018808020001000, 0x0000801000800800,
+  0x0101802800040082, 0x4051000208040100, 0x0001000100020004, 0x0302000041008204,
+  0x4140268000400888, 0x0030024020124000, 0x0809010040102000, 0x005800800d100080,
+  0x0440110008010005, 0x0c04808002000400, 0x02040400098a2810, 0x1000420000840041,
+  0x1100400280018120, 0x20a0200140100040, 0x2100900480200180, 0x0441000900100020,
+  0x0004000808004080, 0x2884004040020100, 0x0200414400481022, 0x00800082000c0869,
+  0x0080002000400042, 0x0920004020401000, 0x0020104501002000, 0x0020800800801000,
+  0x8084800401802800, 0x4001044008012010, 0x0006020804001001, 0x9201048742000413,
+  0x0800800040008020, 0x1001408306060024, 0x0480200010008080, 0x440020400a020010,
+  0x0100080004008080, 0x0002000400028080, 0x8814420801440050, 0x8001205408820001,
+  0x4043400080082880, 0x0210004000200a40, 0x0000809000200180, 0x601000a059001500,
+  0x8802002048102600, 0x8000800400020080, 0x4200112810820400, 0xa006440045038600,
+  0x04028003a0124901, 0x120610c882002102, 0x0010400820001101, 0x4042041001002109,
+  0x1a4a002008041002, 0x0087000804000201, 0xe080211020822804, 0x4824004081002402,
+];
+
+#[rustfmt::skip]
+pub const BISHOP_MAGICS: [u64; 64] = [
+  0x0008019022020020, 0x8020010101090100, 0x0404081620400028, 0x1244050202800404,
+  0x0844050410000100, 0x00010420c4000012, 0x0001081124204000, 0x2040840402124200,
+  0x061060c29003030a, 0x4008f81024005040, 0x1000046424004188, 0x9208a80600400800,
+  0x0002811040100000, 0x4191008820080014, 0x0814508411084040, 0x0144020042121080,
+  0x0008012002c44818, 0x0016102006424600, 0x0230403204040322, 0x2008008082084040,
+  0x0556000400942210, 0x400a01484100a000, 0x000a010401210820, 0x58044100ac063804,
+  0x042044001110aa00, 0x8004d0020c490800, 0x8000500488008010, 0x0401004004004200,
+  0x0006040082008200, 0x07040900049000a4, 0x02020e0102809088, 0x2024004140390400,
+  0x0042484010a00200, 0x40040403c2441000, 0x8000804110100410, 0x0010400822420200,
+  0x00280e0400421100, 0x0221080108820100, 0x0101420080041400, 0x0001006100120100,
+  0x8005112030102234, 0x0020441088140441, 0x8a01001088801000, 0x1008084010400200,
+  0x0864081014000040, 0x0230200241004091, 0x1e0802040c1a2044, 0x0008880084200081,
+  0x0001011030044112, 0x40e0404210500200, 0x0000002108188000, 0x0009144046081100,
+  0x0000100421860002, 0x020411221001105c, 0x1040300200850800, 0x20101000c1004613,
+  0x008a010082100200, 0x910000b404022820, 0x0010222200441200, 0x0201004020840402,
+  0x9011408840082200, 0x0009000420080249, 0x0d108510500200a0, 0x0040100401003020,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Slider {
+    Rook,
+    Bishop,
+}
+
+impl Slider {
+    pub fn occupancy(self, square: u8) -> u64 {
+        match self {
+            Slider::Rook => get_rook_occupancy(square),
+            Slider::Bishop => get_bishop_occupancy(square),
+        }
+    }
+
+    pub fn attacks(self, square: u8, blockers: u64) -> u64 {
+        match self {
+            Slider::Rook => get_rook_attacks(square, blockers),
+            Slider::Bishop => get_bishop_attacks(square, blockers),
+        }
+    }
+}
+
+// Maps an index to one of the possible subsets of the relevant occupancy mask,
+// so that iterating the index over 0..2^bits enumerates all blocker setups
+//
+// https://www.chessprogramming.org/Traversing_Subsets_of_a_Set
+pub fn set_occupancy(index: usize, mask: u64) -> u64 {
+    let mut occupancy = 0;
+    let mut mask = mask;
+    let mut bit = 0;
+
+    while mask != 0 {
+        let square = pop_ls1b(&mut mask);
+        if index & (1 << bit) != 0 {
+            set_bit(&mut occupancy, square);
+        }
+        bit += 1;
+    }
+
+    occupancy
+}
+
+#[inline]
+pub fn get_magic_index(occupancy: u64, magic: u64, relevant_bits: u32) -> usize {
+    (occupancy.wrapping_mul(magic) >> (64 - relevant_bits)) as usize
+}
+
+// Brute-forces a magic number which maps every relevant occupancy of a square
+// to a table index without destructive collisions
+//
+// https://www.chessprogramming.org/Looking_for_Magics
+pub fn find_magic_number(square: u8, slider: Slider, random: &mut Random) -> u64 {
+    let mask = slider.occupancy(square);
+    let relevant_bits = count_bits(mask);
+    let count = 1 << relevant_bits;
+
+    let occupancies: Vec<u64> = (0..count).map(|index| set_occupancy(index, mask)).collect();
+    let attacks: Vec<u64> = occupancies.iter().map(|&occ| slider.attacks(square, occ)).collect();
+    let mut used = vec![0u64; count];
+
+    loop {
+        let magic = random.sparse_u64();
+        if count_bits(mask.wrapping_mul(magic) & 0xFF00_0000_0000_0000) < 6 {
+            continue;
+        }
+
+        used.iter_mut().for_each(|entry| *entry = 0);
+
+        // Attack sets are never empty, so 0 marks an unused table entry
+        let valid = occupancies.iter().zip(&attacks).all(|(&occupancy, &attack)| {
+            let entry = &mut used[get_magic_index(occupancy, magic, relevant_bits)];
+            if *entry == 0 {
+                *entry = attack;
+            }
+            *entry == attack
+        });
+
+        if valid {
+            return magic;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::Square::*;
+    use crate::test::*;
+
+    #[test]
+    fn enumerates_occupancies() {
+        let mask = bitboard_with(vec![B2, E4, G7]);
+
+        assert_eq!(set_occupancy(0, mask), 0);
+        assert_eq!(set_occupancy(0b111, mask), mask);
+        verify_bitboard(set_occupancy(0b101, mask), vec![G7, B2]);
+    }
+
+    #[test]
+    fn finds_magic_numbers() {
+        let mut random = Random::new(MAGIC_SEED);
+
+        for &(square, slider) in &[(A1, Slider::Rook), (E4, Slider::Bishop), (D5, Slider::Rook)] {
+            let magic = find_magic_number(square as u8, slider, &mut random);
+            let mask = slider.occupancy(square as u8);
+            let bits = count_bits(mask);
+            let mut table = vec![None; 1 << bits];
+
+            for index in 0..(1 << bits) {
+                let occupancy = set_occupancy(index, mask);
+                let attacks = slider.attacks(square as u8, occupancy);
+                let entry = &mut table[get_magic_index(occupancy, magic, bits)];
+                assert!(entry.is_none() || *entry == Some(attacks));
+                *entry = Some(attacks);
+            }
+        }
+    }
+
+    #[test]
+    fn reproduces_hardcoded_magics() {
+        let mut random = Random::new(MAGIC_SEED);
+
+        assert_eq!(find_magic_number(0, Slider::Rook, &mut random), ROOK_MAGICS[0]);
+        assert_eq!(find_magic_number(1, Slider::Rook, &mut random), ROOK_MAGICS[1]);
+    }
+}
@@ -1 +1,3 @@
-fn main() {}
+fn main() {
+    rschess::uci::run();
+}
@@ -0,0 +1,291 @@
+use crate::defs::{Bitboard, Castling, CastlingRights, Side, Square};
+use crate::movegen::{castling_index, en_passant_victim, CASTLING_MOVES};
+use crate::moves::Move;
+use crate::position::Position;
+use crate::zobrist::{castling_key, en_passant_key, side_key};
+
+// Position state which cannot be recovered from the move itself,
+// needed to take the move back
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Undo {
+    pub captured: Option<usize>,
+    pub en_passant_square: u8,
+    pub castling_rights: CastlingRights,
+    pub fifty_move_count: u16,
+    pub key: u64,
+}
+
+impl Position {
+    // Plays a pseudo-legal move, updating the position incrementally
+    // Returns the state needed to take the move back with `unmake_move`
+    //
+    // https://www.chessprogramming.org/Make_Move
+    pub fn make_move(&mut self, m: Move) -> Undo {
+        let side = self.side_to_move;
+        let (source, target, piece) = (m.source(), m.target(), m.piece());
+
+        let mut undo = Undo {
+            captured: None,
+            en_passant_square: self.en_passant_square,
+            castling_rights: self.castling_rights,
+            fifty_move_count: self.fifty_move_count,
+            key: self.key,
+        };
+
+        if m.is_capture() {
+            let square = captured_square(side, m);
+            undo.captured = self.piece_at(square);
+            if let Some(captured) = undo.captured {
+                self.remove_piece(captured, square);
+            }
+        }
+
+        if m.is_castling() {
+            // Both pieces are lifted first, as in Chess960 either of them
+            // may land on the square the other one starts from
+            let (king_target, rook, rook_source, rook_target) = self.castling_squares(side, m);
+            self.remove_piece(piece, source);
+            self.remove_piece(rook, rook_source);
+            self.add_piece(piece, king_target);
+            self.add_piece(rook, rook_target);
+        } else {
+            self.remove_piece(piece, source);
+            self.add_piece(m.promoted().unwrap_or(piece), target);
+        }
+
+        self.key ^= en_passant_key(self.en_passant_square) ^ castling_key(self.castling_rights);
+        self.en_passant_square =
+            if m.is_double_push() { (source + target) / 2 } else { Square::NoSquare as u8 };
+        self.castling_rights &=
+            !(self.castling_rights_lost(source) | self.castling_rights_lost(target));
+        if piece == Bitboard::WHITE_KING + side.piece_offset() {
+            self.castling_rights &= !castling_rights_of(side);
+        }
+        self.key ^= en_passant_key(self.en_passant_square) ^ castling_key(self.castling_rights);
+
+        let is_pawn = piece == Bitboard::WHITE_PAWNS + side.piece_offset();
+        self.fifty_move_count =
+            if is_pawn || m.is_capture() { 0 } else { self.fifty_move_count + 1 };
+        self.halfmove_count += 1;
+        self.side_to_move = !side;
+        self.key ^= side_key();
+
+        debug_assert_eq!(self.key, self.compute_key(), "incremental hash key mismatch");
+
+        undo
+    }
+
+    // Takes back a move played with `make_move`, restoring the position exactly
+    pub fn unmake_move(&mut self, m: Move, undo: Undo) {
+        self.side_to_move = !self.side_to_move;
+        self.halfmove_count -= 1;
+
+        let side = self.side_to_move;
+        let (source, target, piece) = (m.source(), m.target(), m.piece());
+
+        if m.is_castling() {
+            let (king_target, rook, rook_source, rook_target) = self.castling_squares(side, m);
+            self.remove_piece(piece, king_target);
+            self.remove_piece(rook, rook_target);
+            self.add_piece(piece, source);
+            self.add_piece(rook, rook_source);
+        } else {
+            self.remove_piece(m.promoted().unwrap_or(piece), target);
+            self.add_piece(piece, source);
+        }
+
+        if let Some(captured) = undo.captured {
+            self.add_piece(captured, captured_square(side, m));
+        }
+
+        self.en_passant_square = undo.en_passant_square;
+        self.castling_rights = undo.castling_rights;
+        self.fifty_move_count = undo.fifty_move_count;
+        self.key = undo.key;
+    }
+}
+
+#[inline]
+fn captured_square(side: Side, m: Move) -> u8 {
+    if m.is_en_passant() {
+        en_passant_victim(side, m.target())
+    } else {
+        m.target()
+    }
+}
+
+impl Position {
+    // Returns the king target square, the rook bitboard index, and the rook
+    // source and target squares of a castling move
+    fn castling_squares(&self, side: Side, m: Move) -> (u8, usize, u8, u8) {
+        let index = castling_index(side, m);
+        let castling = &CASTLING_MOVES[index];
+
+        (
+            castling.king_target as u8,
+            Bitboard::WHITE_ROOKS + side.piece_offset(),
+            self.castling_rooks[index],
+            castling.rook_target as u8,
+        )
+    }
+
+    // Returns castling rights revoked when a piece moves from or to the square
+    // of a castling rook (the rook moves away or gets captured)
+    #[inline]
+    fn castling_rights_lost(&self, square: u8) -> CastlingRights {
+        CASTLING_MOVES
+            .iter()
+            .zip(self.castling_rooks)
+            .filter(|(_, rook)| *rook == square)
+            .fold(0, |rights, (castling, _)| rights | castling.right)
+    }
+}
+
+#[inline]
+fn castling_rights_of(side: Side) -> CastlingRights {
+    match side {
+        Side::White => Castling::WK | Castling::WQ,
+        Side::Black => Castling::BK | Castling::BQ,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::{Castling, Square::*};
+    use crate::test::*;
+
+    fn find_move(position: &Position, m: &str) -> Move {
+        *position.legal_moves().iter().find(|legal| legal.to_string() == m).unwrap()
+    }
+
+    fn play(position: &mut Position, m: &str) -> Undo {
+        let m = find_move(position, m);
+        position.make_move(m)
+    }
+
+    #[test]
+    fn restores_position_after_every_move() {
+        let fens = [
+            crate::constants::STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/1P6/8/8/8/8/1p6/R3K2R b KQkq - 3 20",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            "rk3r2/8/8/8/8/8/8/RK3R2 b FAfa - 0 1",
+        ];
+
+        for fen in &fens {
+            let mut position = Position::from_fen(fen).unwrap();
+            let original = position.clone();
+
+            for m in original.legal_moves() {
+                let undo = position.make_move(m);
+                assert_ne!(position, original);
+                position.unmake_move(m, undo);
+                assert_eq!(position, original, "{} {}", fen, m);
+            }
+        }
+    }
+
+    #[test]
+    fn updates_side_and_clocks() {
+        let mut position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+
+        play(&mut position, "g1f3");
+        assert_eq!(position.side_to_move, Side::Black);
+        assert_eq!((position.fifty_move_count, position.halfmove_count), (1, 3));
+
+        play(&mut position, "g8f6");
+        assert_eq!((position.fifty_move_count, position.halfmove_count), (2, 4));
+
+        play(&mut position, "e2e4");
+        assert_eq!((position.fifty_move_count, position.halfmove_count), (0, 5));
+        assert_eq!(position.en_passant_square, E3 as u8);
+
+        play(&mut position, "f6e4");
+        assert_eq!(position.fifty_move_count, 0);
+        assert_eq!(position.en_passant_square, NoSquare as u8);
+    }
+
+    #[test]
+    fn captures_en_passant() {
+        let mut position = Position::from_fen("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 2").unwrap();
+        let original = position.clone();
+
+        let m = find_move(&position, "d5e6");
+        let undo = position.make_move(m);
+
+        assert_eq!(undo.captured, Some(Bitboard::BLACK_PAWNS));
+        verify_pos_bitboard(&position, Bitboard::WHITE_PAWNS, vec![E6]);
+        verify_pos_bitboard(&position, Bitboard::BLACK_PAWNS, vec![]);
+        verify_pos_bitboard(&position, Bitboard::ALL_PIECES, vec![E8, E6, E1]);
+
+        position.unmake_move(m, undo);
+        assert_eq!(position, original);
+    }
+
+    #[test]
+    fn promotes_pawn() {
+        let mut position = Position::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        play(&mut position, "a7b8n");
+
+        verify_pos_bitboard(&position, Bitboard::WHITE_PAWNS, vec![]);
+        verify_pos_bitboard(&position, Bitboard::WHITE_KNIGHTS, vec![B8]);
+        verify_pos_bitboard(&position, Bitboard::BLACK_KNIGHTS, vec![]);
+        verify_pos_bitboard(&position, Bitboard::WHITE_PIECES, vec![B8, E1]);
+    }
+
+    #[test]
+    fn castles() {
+        let mut position = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+
+        play(&mut position, "e1g1");
+        verify_pos_bitboard(&position, Bitboard::WHITE_KING, vec![G1]);
+        verify_pos_bitboard(&position, Bitboard::WHITE_ROOKS, vec![A1, F1]);
+        assert_eq!(position.castling_rights, Castling::BK | Castling::BQ);
+
+        play(&mut position, "e8c8");
+        verify_pos_bitboard(&position, Bitboard::BLACK_KING, vec![C8]);
+        verify_pos_bitboard(&position, Bitboard::BLACK_ROOKS, vec![D8, H8]);
+        assert_eq!(position.castling_rights, 0);
+    }
+
+    #[test]
+    fn castles_in_chess960() {
+        let mut position = Position::from_fen("1r3kr1/1p6/8/8/8/8/6P1/RK4R1 w AGbg - 0 1").unwrap();
+
+        // The king and the rook swap squares
+        play(&mut position, "b1a1");
+        verify_pos_bitboard(&position, Bitboard::WHITE_KING, vec![C1]);
+        verify_pos_bitboard(&position, Bitboard::WHITE_ROOKS, vec![D1, G1]);
+        assert_eq!(position.castling_rights, Castling::BK | Castling::BQ);
+
+        play(&mut position, "f8g8");
+        verify_pos_bitboard(&position, Bitboard::BLACK_KING, vec![G8]);
+        verify_pos_bitboard(&position, Bitboard::BLACK_ROOKS, vec![B8, F8]);
+        assert_eq!(position.castling_rights, 0);
+    }
+
+    #[test]
+    fn revokes_castling_rights() {
+        let mut position = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+
+        play(&mut position, "h1h2");
+        assert_eq!(position.castling_rights, Castling::WQ | Castling::BK | Castling::BQ);
+
+        play(&mut position, "e8d8");
+        assert_eq!(position.castling_rights, Castling::WQ);
+
+        play(&mut position, "a1a8");
+        assert_eq!(position.castling_rights, 0);
+
+        // Rights belong to the rooks given by their files
+        let mut position = Position::from_fen("rr2k3/8/8/8/8/8/8/RR2K3 w Bb - 0 1").unwrap();
+        play(&mut position, "a1a2");
+        assert_eq!(position.castling_rights, Castling::WQ | Castling::BQ);
+        play(&mut position, "b8b1");
+        assert_eq!(position.castling_rights, 0);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::attacks::*;
+use crate::bitboard::*;
+use crate::constants::{RANK_1, RANK_2, RANK_7, RANK_8};
+use crate::defs::{Bitboard, Castling, CastlingRights, Side, Square, Square::*};
+use crate::moves::Move;
+use crate::position::Position;
+
+const PROMOTIONS: [usize; 4] = [
+    Bitboard::WHITE_QUEENS,
+    Bitboard::WHITE_ROOKS,
+    Bitboard::WHITE_BISHOPS,
+    Bitboard::WHITE_KNIGHTS,
+];
+
+// Castling of a side towards one of the wings. The king and rook end up on
+// the same squares in Chess960 as in standard chess, while the rook starts
+// on the square stored with the castling right in the position
+//
+// https://www.chessprogramming.org/Castling#Chess960
+pub(crate) struct CastlingMove {
+    pub right: CastlingRights,
+    pub side: Side,
+    pub king_target: Square,
+    pub rook_target: Square,
+}
+
+// Castling moves ordered like the castling rights bits
+#[rustfmt::skip]
+pub(crate) const CASTLING_MOVES: [CastlingMove; 4] = [
+    CastlingMove { right: Castling::WK, side: Side::White, king_target: G1, rook_target: F1 },
+    CastlingMove { right: Castling::WQ, side: Side::White, king_target: C1, rook_target: D1 },
+    CastlingMove { right: Castling::BK, side: Side::Black, king_target: G8, rook_target: F8 },
+    CastlingMove { right: Castling::BQ, side: Side::Black, king_target: C8, rook_target: D8 },
+];
+
+// Squares of the castling rooks in standard chess
+pub(crate) const STANDARD_CASTLING_ROOKS: [u8; 4] = [H1 as u8, A1 as u8, H8 as u8, A8 as u8];
+
+impl Position {
+    // Generates all legal moves in the position
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.pseudo_legal_moves().into_iter().filter(|&m| self.is_legal(m)).collect()
+    }
+
+    // Generates moves which follow the piece movement rules, but may leave
+    // the king of the side to move in check
+    //
+    // https://www.chessprogramming.org/Pseudo-Legal_Move
+    pub fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(64);
+
+        self.generate_pawn_moves(&mut moves);
+        self.generate_piece_moves(&mut moves);
+        self.generate_castling_moves(&mut moves);
+
+        moves
+    }
+
+    // Checks whether a pseudo-legal move does not leave the own king in check
+    pub fn is_legal(&self, m: Move) -> bool {
+        let side = self.side_to_move;
+        let mut after = self.clone();
+
+        if m.is_capture() {
+            let square =
+                if m.is_en_passant() { en_passant_victim(side, m.target()) } else { m.target() };
+            if let Some(captured) = after.piece_at(square) {
+                after.remove_piece(captured, square);
+            }
+        }
+
+        // The castling rook may have shielded the king's target square
+        if m.is_castling() {
+            after.make_move(m);
+        } else {
+            after.remove_piece(m.piece(), m.source());
+            after.add_piece(m.piece(), m.target());
+        }
+
+        let king = after.bitboards[Bitboard::WHITE_KING + side.piece_offset()];
+        !after.is_square_attacked(get_ls1b_index(king), !side)
+    }
+
+    fn generate_pawn_moves(&self, moves: &mut Vec<Move>) {
+        let side = self.side_to_move;
+        let offset = side.piece_offset();
+        let piece = Bitboard::WHITE_PAWNS + offset;
+        let occupancy = self.bitboards[Bitboard::ALL_PIECES];
+        let enemies = self.bitboards[(!side).pieces_index()];
+
+        let (forward, start_rank, promotion_rank): (i8, u64, u64) = match side {
+            Side::White => (-8, RANK_2, RANK_8),
+            Side::Black => (8, RANK_7, RANK_1),
+        };
+
+        let mut add_move = |source: u8, target: u8, flags: u32| {
+            if get_bit(promotion_rank, target) != 0 {
+                for promoted in &PROMOTIONS {
+                    moves.push(Move::new(source, target, piece, Some(promoted + offset), flags));
+                }
+            } else {
+                moves.push(Move::new(source, target, piece, None, flags));
+            }
+        };
+
+        let mut pawns = self.bitboards[piece];
+        while pawns != 0 {
+            let source = pop_ls1b(&mut pawns);
+
+            let target = (source as i8 + forward) as u8;
+            if get_bit(occupancy, target) == 0 {
+                add_move(source, target, 0);
+
+                let double_target = (target as i8 + forward) as u8;
+                if get_bit(start_rank, source) != 0 && get_bit(occupancy, double_target) == 0 {
+                    add_move(source, double_target, Move::DOUBLE_PUSH);
+                }
+            }
+
+            let mut attacks = pawn_attacks(side, source) & enemies;
+            while attacks != 0 {
+                add_move(source, pop_ls1b(&mut attacks), Move::CAPTURE);
+            }
+
+            if self.en_passant_square != Square::NoSquare as u8
+                && get_bit(pawn_attacks(side, source), self.en_passant_square) != 0
+            {
+                add_move(source, self.en_passant_square, Move::CAPTURE | Move::EN_PASSANT);
+            }
+        }
+    }
+
+    fn generate_piece_moves(&self, moves: &mut Vec<Move>) {
+        let side = self.side_to_move;
+        let offset = side.piece_offset();
+        let occupancy = self.bitboards[Bitboard::ALL_PIECES];
+        let own = self.bitboards[side.pieces_index()];
+        let enemies = self.bitboards[(!side).pieces_index()];
+
+        #[rustfmt::skip]
+        let pieces = [
+            Bitboard::WHITE_KNIGHTS, Bitboard::WHITE_BISHOPS, Bitboard::WHITE_ROOKS,
+            Bitboard::WHITE_QUEENS, Bitboard::WHITE_KING,
+        ];
+
+        for &piece in &pieces {
+            let mut bitboard = self.bitboards[piece + offset];
+
+            while bitboard != 0 {
+                let source = pop_ls1b(&mut bitboard);
+                let mut attacks = piece_attacks(piece, source, occupancy) & !own;
+
+                while attacks != 0 {
+                    let target = pop_ls1b(&mut attacks);
+                    let flags = if get_bit(enemies, target) != 0 { Move::CAPTURE } else { 0 };
+                    moves.push(Move::new(source, target, piece + offset, None, flags));
+                }
+            }
+        }
+    }
+
+    // Generates castling moves, which require the squares passed by the king
+    // and the rook to be empty and the squares passed by the king to be safe.
+    // In Chess960 the move is encoded as the king capturing its own rook
+    fn generate_castling_moves(&self, moves: &mut Vec<Move>) {
+        let side = self.side_to_move;
+        let offset = side.piece_offset();
+        let king_bitboard = self.bitboards[Bitboard::WHITE_KING + offset];
+        if king_bitboard == 0 {
+            return;
+        }
+
+        let king = get_ls1b_index(king_bitboard);
+        for (index, castling) in CASTLING_MOVES.iter().enumerate().filter(|(_, c)| c.side == side) {
+            let (king_target, rook_target) =
+                (castling.king_target as u8, castling.rook_target as u8);
+            let rook = self.castling_rooks[index];
+            let occupancy = self.bitboards[Bitboard::ALL_PIECES] & !(1 << king) & !(1 << rook);
+            let king_path = king.min(king_target)..=king.max(king_target);
+            let rook_path = rook.min(rook_target)..=rook.max(rook_target);
+
+            if self.castling_rights & castling.right == 0
+                || coords(king).0 != coords(king_target).0
+                || get_bit(self.bitboards[Bitboard::WHITE_ROOKS + offset], rook) == 0
+                || king_path.clone().chain(rook_path).any(|square| get_bit(occupancy, square) != 0)
+                || king_path.into_iter().any(|square| self.is_square_attacked(square, !side))
+            {
+                continue;
+            }
+
+            let target = if self.chess960 { rook } else { king_target };
+            moves.push(Move::new(
+                king,
+                target,
+                Bitboard::WHITE_KING + offset,
+                None,
+                Move::CASTLING,
+            ));
+        }
+    }
+}
+
+// Returns the index of the castling move made by the king, which moves towards
+// its rook in Chess960 and towards its target square in standard chess
+#[inline]
+pub(crate) fn castling_index(side: Side, m: Move) -> usize {
+    side as usize * 2 + if m.target() > m.source() { 0 } else { 1 }
+}
+
+// Returns attacks of a piece, given by its white bitboard index
+#[inline]
+pub(crate) fn piece_attacks(piece: usize, square: u8, occupancy: u64) -> u64 {
+    match piece {
+        Bitboard::WHITE_KNIGHTS => knight_attacks(square),
+        Bitboard::WHITE_BISHOPS => bishop_attacks(square, occupancy),
+        Bitboard::WHITE_ROOKS => rook_attacks(square, occupancy),
+        Bitboard::WHITE_QUEENS => queen_attacks(square, occupancy),
+        Bitboard::WHITE_KING => king_attacks(square),
+        _ => unreachable!(),
+    }
+}
+
+// Returns the square of a pawn captured en passant
+#[inline]
+pub(crate) fn en_passant_victim(side: Side, target: u8) -> u8 {
+    match side {
+        Side::White => target + 8,
+        Side::Black => target - 8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_move(moves: &[Move], m: &str) -> bool {
+        moves.iter().any(|generated| generated.to_string() == m)
+    }
+
+    #[test]
+    fn generates_starting_moves() {
+        let moves = Position::from_fen(crate::constants::STARTING_FEN).unwrap().legal_moves();
+
+        assert_eq!(moves.len(), 20);
+        assert!(has_move(&moves, "e2e4"));
+        assert!(has_move(&moves, "g1f3"));
+        assert!(moves.iter().find(|m| m.to_string() == "e2e4").unwrap().is_double_push());
+    }
+
+    #[test]
+    fn counts_moves_in_reference_positions() {
+        let positions = [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 48),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 14),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 6),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 44),
+            ("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", 46),
+        ];
+
+        for (fen, count) in &positions {
+            assert_eq!(Position::from_fen(fen).unwrap().legal_moves().len(), *count, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn generates_en_passant() {
+        let moves = Position::from_fen("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 2").unwrap().legal_moves();
+        let en_passant = moves.iter().find(|m| m.to_string() == "d5e6").unwrap();
+
+        assert!(en_passant.is_en_passant() && en_passant.is_capture());
+    }
+
+    #[test]
+    fn generates_promotions() {
+        let moves = Position::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap().legal_moves();
+
+        for m in &["a7a8q", "a7a8r", "a7a8b", "a7a8n", "a7b8q", "a7b8r", "a7b8b", "a7b8n"] {
+            assert!(has_move(&moves, m));
+        }
+        let black = Position::from_fen("4k3/8/8/8/8/8/p7/4K3 b - - 0 1").unwrap().legal_moves();
+        assert!(has_move(&black, "a2a1n"));
+    }
+
+    #[test]
+    fn generates_castling() {
+        let moves =
+            Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap().legal_moves();
+        assert!(has_move(&moves, "e1g1") && has_move(&moves, "e1c1"));
+
+        let moves = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b Kq - 0 1").unwrap().legal_moves();
+        assert!(!has_move(&moves, "e8g8") && has_move(&moves, "e8c8"));
+    }
+
+    #[test]
+    fn does_not_castle_through_check() {
+        let moves =
+            Position::from_fen("r3k2r/8/8/8/8/8/5r2/R3K2R w KQ - 0 1").unwrap().legal_moves();
+        assert!(!has_move(&moves, "e1g1") && has_move(&moves, "e1c1"));
+
+        let moves = Position::from_fen("4k3/8/8/8/8/8/8/RN2K2R w KQ - 0 1").unwrap().legal_moves();
+        assert!(!has_move(&moves, "e1c1"));
+    }
+
+    #[test]
+    fn generates_chess960_castling() {
+        let moves =
+            Position::from_fen("rk4r1/6p1/8/8/8/8/6P1/RK4R1 w GAga - 0 1").unwrap().legal_moves();
+        let castling: Vec<String> =
+            moves.iter().filter(|m| m.is_castling()).map(|m| m.to_string()).collect();
+        assert_eq!(castling, ["b1g1", "b1a1"]);
+
+        // The rook has to reach d1, and the king must not pass the attacked e1
+        let moves =
+            Position::from_fen("rk2r3/8/8/8/8/8/8/RK1N2R1 w GA - 0 1").unwrap().legal_moves();
+        assert!(!moves.iter().any(|m| m.is_castling()));
+
+        // The castling rook shields the king's target square from a rook behind it
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/rR2K3 w B - 0 1").unwrap();
+        assert!(has_move(&position.pseudo_legal_moves(), "e1b1"));
+        assert!(!has_move(&position.legal_moves(), "e1b1"));
+    }
+
+    #[test]
+    fn filters_illegal_moves() {
+        // The knight is pinned, the pseudo-legal moves include its jumps
+        let position = Position::from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+
+        assert!(has_move(&position.pseudo_legal_moves(), "e2c3"));
+        assert!(!has_move(&position.legal_moves(), "e2c3"));
+    }
+
+    #[test]
+    fn filters_en_passant_discovered_check() {
+        let moves = Position::from_fen("8/8/8/K2Pp2r/8/8/8/7k w - e6 0 2").unwrap().legal_moves();
+        assert!(!has_move(&moves, "d5e6"));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::constants::{HUMAN_SQUARES, PIECE_CHARS};
+use crate::defs::Square;
+use crate::fen::get_square_id;
+use crate::position::Position;
+use std::{error, fmt};
+
+// Moves are encoded in a 32 bit unsigned integer
+//
+// 0000 0000 0000 0000 0011 1111    source square       0x3f
+// 0000 0000 0000 1111 1100 0000    target square       0xfc0
+// 0000 0000 1111 0000 0000 0000    piece               0xf000
+// 0000 1111 0000 0000 0000 0000    promoted piece      0xf0000
+// 0001 0000 0000 0000 0000 0000    capture flag        0x100000
+// 0010 0000 0000 0000 0000 0000    double push flag    0x200000
+// 0100 0000 0000 0000 0000 0000    en passant flag     0x400000
+// 1000 0000 0000 0000 0000 0000    castling flag       0x800000
+//
+// Pieces are stored as position bitboard indexes. A king is never a promotion
+// target, so 0 in the promoted piece field means there is no promotion
+//
+// https://www.chessprogramming.org/Encoding_Moves
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Move(u32);
+
+impl Move {
+    pub const CAPTURE: u32 = 0x100000;
+    pub const DOUBLE_PUSH: u32 = 0x200000;
+    pub const EN_PASSANT: u32 = 0x400000;
+    pub const CASTLING: u32 = 0x800000;
+
+    #[inline]
+    pub fn new(source: u8, target: u8, piece: usize, promoted: Option<usize>, flags: u32) -> Self {
+        Self(
+            source as u32
+                | (target as u32) << 6
+                | (piece as u32) << 12
+                | (promoted.unwrap_or(0) as u32) << 16
+                | flags,
+        )
+    }
+
+    #[inline]
+    pub fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    #[inline]
+    pub fn raw(self) -> u32 {
+        self.0
+    }
+
+    #[inline]
+    pub fn source(self) -> u8 {
+        (self.0 & 0x3f) as u8
+    }
+
+    #[inline]
+    pub fn target(self) -> u8 {
+        ((self.0 & 0xfc0) >> 6) as u8
+    }
+
+    #[inline]
+    pub fn piece(self) -> usize {
+        ((self.0 & 0xf000) >> 12) as usize
+    }
+
+    #[inline]
+    pub fn promoted(self) -> Option<usize> {
+        match (self.0 & 0xf0000) >> 16 {
+            0 => None,
+            piece => Some(piece as usize),
+        }
+    }
+
+    #[inline]
+    pub fn is_capture(self) -> bool {
+        self.0 & Self::CAPTURE != 0
+    }
+
+    #[inline]
+    pub fn is_double_push(self) -> bool {
+        self.0 & Self::DOUBLE_PUSH != 0
+    }
+
+    #[inline]
+    pub fn is_en_passant(self) -> bool {
+        self.0 & Self::EN_PASSANT != 0
+    }
+
+    #[inline]
+    pub fn is_castling(self) -> bool {
+        self.0 & Self::CASTLING != 0
+    }
+
+    // Formats the move in the long algebraic notation used by UCI
+    pub fn to_uci(self) -> String {
+        self.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveParseError {
+    InvalidSyntax(String),
+    IllegalMove(String),
+}
+
+impl fmt::Display for MoveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveParseError::InvalidSyntax(m) => write!(f, "invalid move syntax \"{}\"", m),
+            MoveParseError::IllegalMove(m) => write!(f, "illegal move \"{}\"", m),
+        }
+    }
+}
+
+impl error::Error for MoveParseError {}
+
+impl Position {
+    // Parses a move in long algebraic notation (like "e7e8q"), resolving
+    // castling, en passant and promotion from the position
+    pub fn parse_uci_move(&self, move_str: &str) -> Result<Move, MoveParseError> {
+        let invalid = || MoveParseError::InvalidSyntax(move_str.to_string());
+
+        if !move_str.is_ascii() || (move_str.len() != 4 && move_str.len() != 5) {
+            return Err(invalid());
+        }
+
+        let source = get_square_id(&move_str[0..2]);
+        let target = get_square_id(&move_str[2..4]);
+        if source == Square::NoSquare as u8 || target == Square::NoSquare as u8 {
+            return Err(invalid());
+        }
+
+        let promoted = match move_str[4..].chars().next() {
+            Some(c) => match "qrbn".find(c) {
+                // Promotion pieces follow the queen in the bitboard order
+                Some(index) => Some(index + 1),
+                None => return Err(invalid()),
+            },
+            None => None,
+        };
+
+        self.legal_moves()
+            .into_iter()
+            .find(|m| {
+                m.source() == source
+                    && m.target() == target
+                    && m.promoted().map(|piece| piece % 6) == promoted
+            })
+            .ok_or_else(|| MoveParseError::IllegalMove(move_str.to_string()))
+    }
+}
+
+// Formats the move in long algebraic notation (like "e7e8q")
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            HUMAN_SQUARES[self.source() as usize],
+            HUMAN_SQUARES[self.target() as usize]
+        )?;
+        match self.promoted() {
+            Some(piece) => write!(f, "{}", PIECE_CHARS[piece].to_ascii_lowercase()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::{Bitboard, Square::*};
+
+    #[test]
+    fn encodes_move() {
+        let m = Move::new(
+            E7 as u8,
+            F8 as u8,
+            Bitboard::WHITE_PAWNS,
+            Some(Bitboard::WHITE_QUEENS),
+            Move::CAPTURE,
+        );
+
+        assert_eq!(m.source(), E7 as u8);
+        assert_eq!(m.target(), F8 as u8);
+        assert_eq!(m.piece(), Bitboard::WHITE_PAWNS);
+        assert_eq!(m.promoted(), Some(Bitboard::WHITE_QUEENS));
+        assert!(m.is_capture());
+        assert!(!m.is_double_push() && !m.is_en_passant() && !m.is_castling());
+        assert_eq!(Move::from_raw(m.raw()), m);
+    }
+
+    #[test]
+    fn encodes_flags() {
+        let m = Move::new(E1 as u8, G1 as u8, Bitboard::WHITE_KING, None, Move::CASTLING);
+
+        assert_eq!(m.promoted(), None);
+        assert!(m.is_castling());
+        assert!(!m.is_capture());
+    }
+
+    #[test]
+    fn formats_move() {
+        let m = Move::new(E2 as u8, E4 as u8, Bitboard::WHITE_PAWNS, None, Move::DOUBLE_PUSH);
+        assert_eq!(m.to_string(), "e2e4");
+
+        let m =
+            Move::new(B2 as u8, A1 as u8, Bitboard::BLACK_PAWNS, Some(Bitboard::BLACK_KNIGHTS), 0);
+        assert_eq!(m.to_string(), "b2a1n");
+        assert_eq!(m.to_uci(), "b2a1n");
+    }
+
+    fn parse_uci(fen: &str, m: &str) -> Result<Move, MoveParseError> {
+        Position::from_fen(fen).unwrap().parse_uci_move(m)
+    }
+
+    #[test]
+    fn parses_uci_moves() {
+        let m = parse_uci(crate::constants::STARTING_FEN, "e2e4").unwrap();
+
+        assert_eq!((m.source(), m.target()), (E2 as u8, E4 as u8));
+        assert!(m.is_double_push());
+        assert_eq!(m.to_uci(), "e2e4");
+    }
+
+    #[test]
+    fn resolves_special_uci_moves() {
+        let castling = parse_uci("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1c1").unwrap();
+        assert!(castling.is_castling());
+
+        let en_passant = parse_uci("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 2", "d5e6").unwrap();
+        assert!(en_passant.is_en_passant() && en_passant.is_capture());
+
+        let promotion = parse_uci("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8r").unwrap();
+        assert_eq!(promotion.promoted(), Some(Bitboard::WHITE_ROOKS));
+        assert!(promotion.is_capture());
+
+        let promotion = parse_uci("4k3/8/8/8/8/8/p7/4K3 b - - 0 1", "a2a1n").unwrap();
+        assert_eq!(promotion.promoted(), Some(Bitboard::BLACK_KNIGHTS));
+    }
+
+    #[test]
+    fn rejects_invalid_uci_moves() {
+        let fen = "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        let invalid = |m: &str| Err(MoveParseError::InvalidSyntax(m.to_string()));
+        let illegal = |m: &str| Err(MoveParseError::IllegalMove(m.to_string()));
+
+        assert_eq!(parse_uci(fen, "a7a8"), illegal("a7a8"));
+        assert_eq!(parse_uci(fen, "a7a8k"), invalid("a7a8k"));
+        assert_eq!(parse_uci(fen, "e1e3"), illegal("e1e3"));
+        assert_eq!(parse_uci(fen, "e1e2q"), illegal("e1e2q"));
+        assert_eq!(parse_uci(fen, "i1e2"), invalid("i1e2"));
+        assert_eq!(parse_uci(fen, "e1"), invalid("e1"));
+        assert_eq!(parse_uci(fen, "e1é2"), invalid("e1é2"));
+        assert_eq!(parse_uci(fen, "0000"), invalid("0000"));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::moves::Move;
+use crate::position::Position;
+
+impl Position {
+    // Counts the leaf nodes of the legal move tree of the given depth,
+    // used to verify the move generator against known results
+    //
+    // https://www.chessprogramming.org/Perft
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        moves
+            .into_iter()
+            .map(|m| {
+                let undo = self.make_move(m);
+                let nodes = self.perft(depth - 1);
+                self.unmake_move(m, undo);
+                nodes
+            })
+            .sum()
+    }
+
+    // Runs perft split by the root moves, which helps to locate
+    // the move generator bugs
+    pub fn divide(&mut self, depth: u32) -> Vec<(Move, u64)> {
+        assert!(depth > 0);
+
+        self.legal_moves()
+            .into_iter()
+            .map(|m| {
+                let undo = self.make_move(m);
+                let nodes = self.perft(depth - 1);
+                self.unmake_move(m, undo);
+                (m, nodes)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::STARTING_FEN;
+
+    // https://www.chessprogramming.org/Perft_Results
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_4_MIRRORED: &str =
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str =
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+    const CHESS960_1: &str = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+    const CHESS960_2: &str = "1rqbkrbn/1ppppp1p/1n6/p1N3p1/8/2P4P/PP1PPPP1/1RQBKRBN w FBfb - 0 9";
+
+    fn verify_perft(fen: &str, results: &[u64]) {
+        let mut position = Position::from_fen(fen).unwrap();
+
+        for (depth, &nodes) in results.iter().enumerate() {
+            assert_eq!(position.perft(depth as u32 + 1), nodes, "{} depth {}", fen, depth + 1);
+        }
+        assert_eq!(position, Position::from_fen(fen).unwrap());
+    }
+
+    #[test]
+    fn starting_position() {
+        verify_perft(STARTING_FEN, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn kiwipete() {
+        verify_perft(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn position_3() {
+        verify_perft(POSITION_3, &[14, 191, 2812, 43238]);
+    }
+
+    #[test]
+    fn position_4() {
+        verify_perft(POSITION_4, &[6, 264, 9467]);
+        verify_perft(POSITION_4_MIRRORED, &[6, 264, 9467]);
+    }
+
+    #[test]
+    fn position_5() {
+        verify_perft(POSITION_5, &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn position_6() {
+        verify_perft(POSITION_6, &[46, 2079, 89890]);
+    }
+
+    #[test]
+    #[ignore]
+    fn starting_position_deep() {
+        verify_perft(STARTING_FEN, &[20, 400, 8902, 197281, 4865609]);
+    }
+
+    #[test]
+    #[ignore]
+    fn kiwipete_deep() {
+        verify_perft(KIWIPETE, &[48, 2039, 97862, 4085603]);
+    }
+
+    #[test]
+    #[ignore]
+    fn position_3_deep() {
+        verify_perft(POSITION_3, &[14, 191, 2812, 43238, 674624, 11030083]);
+    }
+
+    #[test]
+    #[ignore]
+    fn position_4_deep() {
+        verify_perft(POSITION_4, &[6, 264, 9467, 422333]);
+        verify_perft(POSITION_4_MIRRORED, &[6, 264, 9467, 422333]);
+    }
+
+    #[test]
+    #[ignore]
+    fn position_5_deep() {
+        verify_perft(POSITION_5, &[44, 1486, 62379, 2103487]);
+    }
+
+    #[test]
+    #[ignore]
+    fn position_6_deep() {
+        verify_perft(POSITION_6, &[46, 2079, 89890, 3894594]);
+    }
+
+    #[test]
+    fn chess960_positions() {
+        verify_perft(CHESS960_1, &[21, 528, 12189]);
+        verify_perft(CHESS960_2, &[29, 502, 14569]);
+    }
+
+    #[test]
+    fn chess960_castling_encoding() {
+        // Castling as the king capturing its rook does not change the counts
+        let mut position = Position::from_fen(KIWIPETE).unwrap();
+        position.chess960 = true;
+
+        assert_eq!(position.perft(3), 97862);
+    }
+
+    #[test]
+    #[ignore]
+    fn chess960_positions_deep() {
+        verify_perft(CHESS960_1, &[21, 528, 12189, 326672, 8146062]);
+        verify_perft(CHESS960_2, &[29, 502, 14569, 287739, 8652810]);
+    }
+
+    #[test]
+    fn divides_root_moves() {
+        let mut position = Position::from_fen(STARTING_FEN).unwrap();
+        let divide = position.divide(3);
+
+        assert_eq!(divide.len(), 20);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+
+        let (_, nodes) = divide.iter().find(|(m, _)| m.to_string() == "e2e4").unwrap();
+        assert_eq!(*nodes, 600);
+    }
+}
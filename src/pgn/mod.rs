@@ -0,0 +1,108 @@
+use crate::constants::STARTING_FEN;
+use crate::fen::FenError;
+use crate::moves::Move;
+use crate::position::Position;
+use std::fmt;
+
+pub mod reader;
+mod writer;
+
+pub use reader::{PgnError, PgnErrorKind, PgnReader};
+
+// Tags every PGN game has to carry, in the export order
+//
+// https://www.chessprogramming.org/Portable_Game_Notation
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    #[default]
+    Unknown,
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PgnGame {
+    // Tags in the order they were given
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    pub result: GameResult,
+}
+
+// A move node of the game tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnMove {
+    pub san: String,
+    pub m: Move,
+    // Numeric Annotation Glyphs, like 1 for "!" or 2 for "?"
+    pub nags: Vec<u8>,
+    pub comment_before: Option<String>,
+    pub comment_after: Option<String>,
+    // Alternatives to this move, each starting from the position before it
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(san: String, m: Move) -> Self {
+        Self {
+            san,
+            m,
+            nags: Vec::new(),
+            comment_before: None,
+            comment_after: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old_value)) => *old_value = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // Returns tags which are not a part of the seven tag roster
+    pub fn extra_tags(&self) -> impl Iterator<Item = &(String, String)> {
+        self.tags.iter().filter(|(tag, _)| !SEVEN_TAG_ROSTER.contains(&tag.as_str()))
+    }
+
+    // Returns the position given by the FEN tag, or the standard starting position
+    pub fn start_position(&self) -> Result<Position, FenError> {
+        Position::from_fen(self.tag("FEN").unwrap_or(STARTING_FEN))
+    }
+
+    // Replays the main line, returning the start position and positions
+    // after each of the moves
+    pub fn mainline_positions(&self) -> Result<Vec<Position>, FenError> {
+        let mut position = self.start_position()?;
+        let mut positions = vec![position.clone()];
+
+        for node in &self.moves {
+            position.make_move(node.m);
+            positions.push(position.clone());
+        }
+
+        Ok(positions)
+    }
+}
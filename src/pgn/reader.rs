@@ -0,0 +1,607 @@
+use super::{GameResult, PgnGame, PgnMove};
+use crate::fen::FenError;
+use crate::position::Position;
+use crate::san::SanError;
+use crate::validate::PositionError;
+use std::io::BufRead;
+use std::{error, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnErrorKind {
+    UnexpectedChar(char),
+    UnexpectedEof,
+    UnterminatedComment,
+    InvalidTag,
+    // Tag pair found inside of a variation
+    UnexpectedTag,
+    BadNag(String),
+    // Annotation or variation with no move before it
+    MissingMove,
+    UnmatchedParenthesis,
+    InvalidFen(FenError),
+    // FEN tag position which cannot occur in a game
+    InvalidPosition(Vec<PositionError>),
+    IllegalMove(SanError),
+    Io(String),
+}
+
+// Error of a single game, located by its 1-based line and column
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PgnErrorKind,
+}
+
+impl fmt::Display for PgnErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            PgnErrorKind::UnexpectedEof => write!(f, "unexpected end of file"),
+            PgnErrorKind::UnterminatedComment => write!(f, "unterminated comment"),
+            PgnErrorKind::InvalidTag => write!(f, "invalid tag pair"),
+            PgnErrorKind::UnexpectedTag => write!(f, "tag pair inside of a variation"),
+            PgnErrorKind::BadNag(nag) => write!(f, "invalid annotation \"{}\"", nag),
+            PgnErrorKind::MissingMove => write!(f, "annotation or variation without a move"),
+            PgnErrorKind::UnmatchedParenthesis => write!(f, "unmatched parenthesis"),
+            PgnErrorKind::InvalidFen(err) => write!(f, "invalid FEN tag: {}", err),
+            PgnErrorKind::InvalidPosition(errors) => {
+                let errors: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+                write!(f, "invalid FEN tag position: {}", errors.join(", "))
+            }
+            PgnErrorKind::IllegalMove(err) => write!(f, "{}", err),
+            PgnErrorKind::Io(err) => write!(f, "read error: {}", err),
+        }
+    }
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl error::Error for PgnError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    OpenVariation,
+    CloseVariation,
+    Nag(u8),
+    MoveNumber,
+    San(String),
+    Result(GameResult),
+}
+
+#[derive(Debug)]
+struct Spanned {
+    token: Token,
+    line: usize,
+    column: usize,
+}
+
+// Splits the input into tokens, reading one line at a time
+struct Lexer<R: BufRead> {
+    input: R,
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl<R: BufRead> Lexer<R> {
+    fn new(input: R) -> Self {
+        Self { input, chars: Vec::new(), pos: 0, line: 0 }
+    }
+
+    fn error(&self, kind: PgnErrorKind) -> PgnError {
+        PgnError { line: self.line, column: self.pos + 1, kind }
+    }
+
+    // Loads the next line, returns false at the end of the input
+    fn read_line(&mut self) -> Result<bool, PgnError> {
+        let mut line = String::new();
+        let read = self
+            .input
+            .read_line(&mut line)
+            .map_err(|err| self.error(PgnErrorKind::Io(err.to_string())))?;
+
+        self.chars = line.chars().collect();
+        self.pos = 0;
+        self.line += 1;
+        Ok(read > 0)
+    }
+
+    fn peek(&mut self) -> Result<Option<char>, PgnError> {
+        while self.pos >= self.chars.len() {
+            if !self.read_line()? {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.chars[self.pos]))
+    }
+
+    // Peeks a character without moving on to the next line
+    fn peek_in_line(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek_in_line().is_some_and(&predicate) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn skip_line_whitespace(&mut self) {
+        self.take_while(|c| c.is_whitespace() && c != '\n');
+    }
+
+    fn expect(&mut self, expected: char, kind: PgnErrorKind) -> Result<(), PgnError> {
+        match self.peek_in_line() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.error(kind)),
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Spanned>, PgnError> {
+        loop {
+            let c = match self.peek()? {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+            let (line, column) = (self.line, self.pos + 1);
+
+            // Lines starting with '%' are escaped from the parsing
+            if c == '%' && self.pos == 0 {
+                self.pos = self.chars.len();
+                continue;
+            }
+            if c.is_whitespace() {
+                self.pos += 1;
+                continue;
+            }
+
+            let token = match c {
+                '[' => self.read_tag()?,
+                '{' => self.read_comment()?,
+                ';' => {
+                    self.pos += 1;
+                    Token::Comment(self.take_while(|c| c != '\n').trim().to_string())
+                }
+                '(' | ')' | '*' => {
+                    self.pos += 1;
+                    match c {
+                        '(' => Token::OpenVariation,
+                        ')' => Token::CloseVariation,
+                        _ => Token::Result(GameResult::Unknown),
+                    }
+                }
+                '$' => {
+                    self.pos += 1;
+                    let nag = self.take_while(|c| c.is_ascii_digit());
+                    Token::Nag(nag.parse().map_err(|_| self.error(PgnErrorKind::BadNag(nag)))?)
+                }
+                '!' | '?' => {
+                    let suffix = self.take_while(|c| c == '!' || c == '?');
+                    Token::Nag(suffix_nag(&suffix).ok_or(PgnError {
+                        line,
+                        column,
+                        kind: PgnErrorKind::BadNag(suffix),
+                    })?)
+                }
+                _ if c.is_ascii_alphanumeric() => self.read_symbol(),
+                _ => return Err(self.error(PgnErrorKind::UnexpectedChar(c))),
+            };
+
+            return Ok(Some(Spanned { token, line, column }));
+        }
+    }
+
+    // Reads a tag pair like [White "Fischer, Robert J."]
+    fn read_tag(&mut self) -> Result<Token, PgnError> {
+        self.pos += 1;
+        self.skip_line_whitespace();
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        if name.is_empty() {
+            return Err(self.error(PgnErrorKind::InvalidTag));
+        }
+
+        self.skip_line_whitespace();
+        self.expect('"', PgnErrorKind::InvalidTag)?;
+        let mut value = String::new();
+        loop {
+            match self.peek_in_line() {
+                Some('"') => break,
+                Some('\\') if matches!(self.chars.get(self.pos + 1), Some('"' | '\\')) => {
+                    value.push(self.chars[self.pos + 1]);
+                    self.pos += 2;
+                }
+                Some(c) if c != '\n' => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+                _ => return Err(self.error(PgnErrorKind::InvalidTag)),
+            }
+        }
+        self.pos += 1;
+
+        self.skip_line_whitespace();
+        self.expect(']', PgnErrorKind::InvalidTag)?;
+        Ok(Token::Tag(name, value))
+    }
+
+    // Reads a brace comment, which may span multiple lines
+    fn read_comment(&mut self) -> Result<Token, PgnError> {
+        let (line, column) = (self.line, self.pos + 1);
+        self.pos += 1;
+
+        let mut text = String::new();
+        loop {
+            text.push_str(&self.take_while(|c| c != '}'));
+            if self.peek_in_line() == Some('}') {
+                self.pos += 1;
+                break;
+            }
+            if !self.read_line()? {
+                return Err(PgnError { line, column, kind: PgnErrorKind::UnterminatedComment });
+            }
+        }
+
+        Ok(Token::Comment(text.split_whitespace().collect::<Vec<_>>().join(" ")))
+    }
+
+    // Reads a move number, a SAN move or a game result
+    fn read_symbol(&mut self) -> Token {
+        let symbol = self.take_while(|c| c.is_ascii_alphanumeric() || "_+#=:-/".contains(c));
+
+        match symbol.as_str() {
+            "1-0" => Token::Result(GameResult::WhiteWins),
+            "0-1" => Token::Result(GameResult::BlackWins),
+            "1/2-1/2" => Token::Result(GameResult::Draw),
+            _ if symbol.chars().all(|c| c.is_ascii_digit()) => {
+                self.take_while(|c| c == '.');
+                Token::MoveNumber
+            }
+            _ => Token::San(symbol),
+        }
+    }
+
+    // Skips the input up to the tag section of the next game, so that
+    // the reader can continue after an error
+    fn skip_game(&mut self) {
+        let starts_tag = |chars: &[char]| chars.iter().find(|c| !c.is_whitespace()) == Some(&'[');
+        let mut seen_movetext = !starts_tag(&self.chars);
+
+        while let Ok(true) = self.read_line() {
+            let is_tag = starts_tag(&self.chars);
+            if is_tag && seen_movetext {
+                return;
+            }
+            if !is_tag && self.chars.iter().any(|c| !c.is_whitespace()) {
+                seen_movetext = true;
+            }
+        }
+    }
+}
+
+// Maps traditional move suffix annotations to their NAGs
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+// Streaming reader of PGN files, yielding one game at a time. After an
+// error the rest of the broken game is skipped, so iteration can continue
+// with the next one.
+//
+// https://www.chessprogramming.org/Portable_Game_Notation
+pub struct PgnReader<R: BufRead> {
+    lexer: Lexer<R>,
+    peeked: Option<Spanned>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(input: R) -> Self {
+        Self { lexer: Lexer::new(input), peeked: None }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Spanned>, PgnError> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.lexer.next_token(),
+        }
+    }
+
+    // Reads the next game, returns None at the end of the input
+    pub fn read_game(&mut self) -> Result<Option<PgnGame>, PgnError> {
+        let mut game = PgnGame::default();
+        let mut fen_span = (self.lexer.line, self.lexer.pos + 1);
+
+        let first = loop {
+            match self.next_token()? {
+                Some(Spanned { token: Token::Tag(name, value), line, column }) => {
+                    if name == "FEN" {
+                        fen_span = (line, column);
+                    }
+                    game.tags.push((name, value));
+                }
+                Some(token) => break Some(token),
+                None if game.tags.is_empty() => return Ok(None),
+                None => break None,
+            }
+        };
+        self.peeked = first;
+
+        let fen_error = |kind| PgnError { line: fen_span.0, column: fen_span.1, kind };
+        let position =
+            game.start_position().map_err(|err| fen_error(PgnErrorKind::InvalidFen(err)))?;
+        // Moves cannot be replayed in impossible positions
+        position.validate().map_err(|errors| fen_error(PgnErrorKind::InvalidPosition(errors)))?;
+        let (moves, result) = self.read_moves(&position, false)?;
+        game.moves = moves;
+        game.result = result;
+
+        Ok(Some(game))
+    }
+
+    // Reads moves up to the game result or the end of a variation. SAN moves
+    // are replayed from the given position, with each variation starting
+    // from the position before the move it replaces.
+    fn read_moves(
+        &mut self,
+        start: &Position,
+        variation: bool,
+    ) -> Result<(Vec<PgnMove>, GameResult), PgnError> {
+        let mut position = start.clone();
+        let mut previous = start.clone();
+        let mut moves: Vec<PgnMove> = Vec::new();
+        let mut comment_before: Option<String> = None;
+
+        loop {
+            let Spanned { token, line, column } = match self.next_token()? {
+                Some(token) => token,
+                None if variation => {
+                    return Err(self.lexer.error(PgnErrorKind::UnexpectedEof));
+                }
+                None => return Ok((moves, GameResult::Unknown)),
+            };
+            let error = |kind| PgnError { line, column, kind };
+
+            match token {
+                // Movetext without a result ends at the tag section of the next game
+                Token::Tag(..) if !variation => {
+                    self.peeked = Some(Spanned { token, line, column });
+                    return Ok((moves, GameResult::Unknown));
+                }
+                Token::Tag(..) => return Err(error(PgnErrorKind::UnexpectedTag)),
+                Token::MoveNumber => (),
+                Token::San(san) => {
+                    let m = position
+                        .parse_san(&san)
+                        .map_err(|err| error(PgnErrorKind::IllegalMove(err)))?;
+                    previous = position.clone();
+                    position.make_move(m);
+
+                    let mut node = PgnMove::new(san, m);
+                    node.comment_before = comment_before.take();
+                    moves.push(node);
+                }
+                Token::Comment(text) => match moves.last_mut() {
+                    Some(node) => match &mut node.comment_after {
+                        Some(comment) => {
+                            comment.push(' ');
+                            comment.push_str(&text);
+                        }
+                        None => node.comment_after = Some(text),
+                    },
+                    None => {
+                        comment_before = match comment_before {
+                            Some(comment) => Some(comment + " " + &text),
+                            None => Some(text),
+                        }
+                    }
+                },
+                Token::Nag(nag) => match moves.last_mut() {
+                    Some(node) => node.nags.push(nag),
+                    None => return Err(error(PgnErrorKind::MissingMove)),
+                },
+                Token::OpenVariation => {
+                    if moves.is_empty() {
+                        return Err(error(PgnErrorKind::MissingMove));
+                    }
+                    let (alternative, _) = self.read_moves(&previous, true)?;
+                    moves.last_mut().unwrap().variations.push(alternative);
+                }
+                Token::CloseVariation if variation => return Ok((moves, GameResult::Unknown)),
+                Token::CloseVariation => return Err(error(PgnErrorKind::UnmatchedParenthesis)),
+                Token::Result(_) if variation => {
+                    return Err(error(PgnErrorKind::UnmatchedParenthesis));
+                }
+                Token::Result(result) => return Ok((moves, result)),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_game() {
+            Ok(game) => game.map(Ok),
+            Err(err) => {
+                self.peeked = None;
+                self.lexer.skip_game();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::STARTING_FEN;
+
+    const GAMES: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+[ECO "C95"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6
+4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7 $1
+11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
+Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
+23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
+hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
+35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
+Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+
+[Event "Casual"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 e5 (1... c5 2. Nf3 (2. c3) 2... d6) 2. Qh5?! Nc6 3. Bc4 Nf6?? 4. Qxf7# 1-0
+"#;
+
+    fn read_all(pgn: &str) -> Vec<Result<PgnGame, PgnError>> {
+        PgnReader::new(pgn.as_bytes()).collect()
+    }
+
+    #[test]
+    fn reads_multiple_games() {
+        let games = read_all(GAMES);
+        assert_eq!(games.len(), 2);
+
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(game.tag("Round"), Some("29"));
+        assert_eq!(game.extra_tags().collect::<Vec<_>>(), [&("ECO".into(), "C95".into())]);
+        assert_eq!(game.result, GameResult::Draw);
+        assert_eq!(game.moves.len(), 85);
+        assert_eq!(
+            game.moves[4].comment_after.as_deref(),
+            Some("This opening is called the Ruy Lopez.")
+        );
+        assert_eq!(game.moves[19].nags, [1]);
+
+        let positions = game.mainline_positions().unwrap();
+        assert_eq!(positions[0].to_fen(), STARTING_FEN);
+        assert_eq!(
+            positions.last().unwrap().to_fen(),
+            "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43"
+        );
+    }
+
+    #[test]
+    fn reads_variations_and_annotations() {
+        let games = read_all(GAMES);
+        let game = games[1].as_ref().unwrap();
+
+        assert_eq!(game.result, GameResult::WhiteWins);
+        assert_eq!(game.moves.len(), 7);
+        assert_eq!(game.moves[2].nags, [6]);
+        assert_eq!(game.moves[5].nags, [4]);
+        assert_eq!(game.moves[6].san, "Qxf7#");
+
+        let variation = &game.moves[1].variations[0];
+        let sans: Vec<&str> = variation.iter().map(|node| node.san.as_str()).collect();
+        assert_eq!(sans, ["c5", "Nf3", "d6"]);
+        assert_eq!(variation[1].variations[0][0].san, "c3");
+    }
+
+    #[test]
+    fn reads_comments() {
+        let pgn = "{Game comment} 1. d4 ; line comment\n d5 {multi\nline} {second} 2. c4 *";
+        let games = read_all(pgn);
+        let game = games[0].as_ref().unwrap();
+
+        assert_eq!(game.moves[0].comment_before.as_deref(), Some("Game comment"));
+        assert_eq!(game.moves[0].comment_after.as_deref(), Some("line comment"));
+        assert_eq!(game.moves[1].comment_after.as_deref(), Some("multi line second"));
+        assert_eq!(game.result, GameResult::Unknown);
+    }
+
+    #[test]
+    fn starts_from_fen_tag() {
+        let pgn = "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]\n\n1... Kd7 2. e4 *";
+        let games = read_all(pgn);
+        let positions = games[0].as_ref().unwrap().mainline_positions().unwrap();
+
+        assert_eq!(positions[2].to_fen(), "8/3k4/8/8/4P3/8/8/4K3 b - e3 0 2");
+    }
+
+    #[test]
+    fn reports_error_location() {
+        let err = read_all("[Event \"?\"]\n\n1. e4 e5 2. Ke3 *").remove(0).unwrap_err();
+        assert_eq!((err.line, err.column), (3, 13));
+        assert_eq!(err.kind, PgnErrorKind::IllegalMove(SanError::IllegalMove("Ke3".into())));
+
+        let err = read_all("[Event \"?]\n").remove(0).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 11));
+        assert_eq!(err.to_string(), "line 1, column 11: invalid tag pair");
+
+        let err = read_all("1. e4 {unterminated\n\n").remove(0).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 7));
+
+        let err = read_all("[FEN \"8/8 w - - 0 1\"]\n*").remove(0).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+        assert_eq!(err.kind, PgnErrorKind::InvalidFen(FenError::RankCount(2)));
+
+        let err = read_all("[Event \"?\"]\n[FEN \"P3k3/8/8/8/8/8/8/4K3 w - - 0 1\"]\n\n1. Kd2 *")
+            .remove(0)
+            .unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert_eq!(err.kind, PgnErrorKind::InvalidPosition(vec![PositionError::PawnOnBackRank(0)]));
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 1: invalid FEN tag position: pawn on back rank square a8"
+        );
+    }
+
+    #[test]
+    fn skips_broken_games() {
+        let pgn = format!(
+            "[Event \"Broken\"]\n\n1. e4 e4 2. d4 *\n\n[Event \"Bad tag]\n\n1. d4 *\n\n{}",
+            GAMES
+        );
+        let games = read_all(&pgn);
+
+        assert_eq!(games.len(), 4);
+        assert!(games[0].is_err());
+        assert!(games[1].is_err());
+        assert_eq!(games[2].as_ref().unwrap().tag("Event"), Some("F/S Return Match"));
+        assert_eq!(games[3].as_ref().unwrap().tag("Event"), Some("Casual"));
+    }
+
+    #[test]
+    fn reads_games_without_results() {
+        let games = read_all("[Event \"A\"]\n1. e4\n[Event \"B\"]\n1. d4 0-1\n");
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].as_ref().unwrap().moves.len(), 1);
+        assert_eq!(games[0].as_ref().unwrap().result, GameResult::Unknown);
+        assert_eq!(games[1].as_ref().unwrap().result, GameResult::BlackWins);
+    }
+}
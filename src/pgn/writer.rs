@@ -0,0 +1,212 @@
+use super::{PgnGame, PgnMove, SEVEN_TAG_ROSTER};
+use crate::constants::STARTING_FEN;
+use crate::fen::FenError;
+
+// Maximum length of an exported movetext line
+const LINE_WIDTH: usize = 80;
+
+impl PgnGame {
+    // Exports the game in PGN export format: the seven tag roster first,
+    // SetUp and FEN tags for non-standard start positions, then the other
+    // tags and movetext wrapped at 80 columns. Fails on an invalid FEN tag,
+    // which would make the exported game unreadable
+    //
+    // https://www.chessprogramming.org/Portable_Game_Notation
+    pub fn to_pgn(&self) -> Result<String, FenError> {
+        let mut pgn = String::new();
+        let start = self.start_position()?;
+
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.to_string(),
+                "Date" => self.tag(name).unwrap_or("????.??.??").to_string(),
+                _ => self.tag(name).unwrap_or("?").to_string(),
+            };
+            pgn.push_str(&format_tag(name, &value));
+        }
+
+        if start.to_fen() != STARTING_FEN {
+            pgn.push_str(&format_tag("SetUp", "1"));
+            pgn.push_str(&format_tag("FEN", self.tag("FEN").unwrap_or_default()));
+        }
+
+        for (name, value) in self.extra_tags().filter(|(name, _)| name != "SetUp" && name != "FEN")
+        {
+            pgn.push_str(&format_tag(name, value));
+        }
+
+        let mut tokens = Tokens::default();
+        tokens.push_moves(&self.moves, start.halfmove_count);
+        tokens.push(self.result.to_string());
+
+        pgn.push('\n');
+        for line in tokens.wrap() {
+            pgn.push_str(&line);
+            pgn.push('\n');
+        }
+        Ok(pgn)
+    }
+}
+
+fn format_tag(name: &str, value: &str) -> String {
+    format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Movetext split into the units which can be wrapped between
+#[derive(Default)]
+struct Tokens {
+    tokens: Vec<String>,
+    // Opening parentheses to be attached to the next token
+    open: usize,
+}
+
+impl Tokens {
+    fn push(&mut self, token: String) {
+        self.tokens.push("(".repeat(self.open) + &token);
+        self.open = 0;
+    }
+
+    fn push_comment(&mut self, comment: &str) {
+        let words: Vec<&str> = comment.split_whitespace().collect();
+        match words.split_last() {
+            Some((last, [])) => self.push(format!("{{{}}}", last)),
+            Some((last, words)) => {
+                self.push(format!("{{{}", words[0]));
+                for word in &words[1..] {
+                    self.push(word.to_string());
+                }
+                self.push(format!("{}}}", last));
+            }
+            None => self.push("{}".to_string()),
+        }
+    }
+
+    // Pushes a sequence of moves starting at the given ply, where the move
+    // number is required before White moves, and before Black moves at the
+    // start of a sequence or after a comment or variation
+    fn push_moves(&mut self, moves: &[PgnMove], start_ply: u16) {
+        let mut needs_number = true;
+
+        for (ply, node) in (start_ply..).zip(moves) {
+            if let Some(comment) = &node.comment_before {
+                self.push_comment(comment);
+            }
+
+            if ply.is_multiple_of(2) {
+                self.push(format!("{}.", ply / 2));
+            } else if needs_number {
+                self.push(format!("{}...", ply / 2));
+            }
+            self.push(node.san.clone());
+            for nag in &node.nags {
+                self.push(format!("${}", nag));
+            }
+
+            needs_number = false;
+            if let Some(comment) = &node.comment_after {
+                self.push_comment(comment);
+                needs_number = true;
+            }
+            for variation in node.variations.iter().filter(|variation| !variation.is_empty()) {
+                self.open += 1;
+                self.push_moves(variation, ply);
+                self.tokens.last_mut().unwrap().push(')');
+                needs_number = true;
+            }
+        }
+    }
+
+    // Joins the tokens into lines of at most 80 characters
+    fn wrap(self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+
+        for token in self.tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                lines.push(line);
+                line = String::new();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+
+        lines.push(line);
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::{GameResult, PgnReader};
+
+    fn read(pgn: &str) -> PgnGame {
+        PgnReader::new(pgn.as_bytes()).next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn writes_tags_and_movetext() {
+        let mut game = read("1. e4 e5 (1... c5 2. Nf3 (2. c3) d6) 2. Qh5 $6 {Early queen} Nc6 1-0");
+        game.set_tag("White", "Kasparov, \"Garry\"");
+        game.set_tag("ECO", "C20");
+
+        assert_eq!(
+            game.to_pgn().unwrap(),
+            r#"[Event "?"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "Kasparov, \"Garry\""]
+[Black "?"]
+[Result "1-0"]
+[ECO "C20"]
+
+1. e4 e5 (1... c5 2. Nf3 (2. c3) 2... d6) 2. Qh5 $6 {Early queen} 2... Nc6 1-0
+"#
+        );
+    }
+
+    #[test]
+    fn numbers_moves_from_black_to_move() {
+        let game = read(
+            "[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n[SetUp \"1\"]\n\n12... Kd7 13. e4 Ke6 *",
+        );
+        let pgn = game.to_pgn().unwrap();
+
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n"));
+        assert!(pgn.ends_with("\n12... Kd7 13. e4 Ke6 *\n"));
+    }
+
+    #[test]
+    fn omits_fen_for_standard_start() {
+        let game = read(&format!("[FEN \"{}\"]\n\n1. d4 *", STARTING_FEN));
+
+        assert!(!game.to_pgn().unwrap().contains("FEN"));
+        assert!(!game.to_pgn().unwrap().contains("SetUp"));
+    }
+
+    #[test]
+    fn rejects_invalid_fen_tag() {
+        let mut game = read("1. d4 *");
+        game.set_tag("FEN", "4k3/8/8 w - - 0 1");
+
+        assert_eq!(game.to_pgn(), Err(FenError::RankCount(3)));
+    }
+
+    #[test]
+    fn wraps_lines() {
+        let pgn = r#"1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez and it is one
+of the oldest openings} 3... a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3
+O-O 9. h3 Nb8 10. d4 Nbd7 11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 1/2-1/2"#;
+        let game = read(pgn);
+        let exported = game.to_pgn().unwrap();
+        let movetext: Vec<&str> = exported.lines().skip(8).collect();
+
+        assert!(movetext.iter().all(|line| line.len() <= LINE_WIDTH));
+        assert!(movetext.iter().any(|line| line.len() > LINE_WIDTH - 10));
+        assert_eq!(read(&exported).moves, game.moves);
+        assert_eq!(read(&exported).result, GameResult::Draw);
+    }
+}
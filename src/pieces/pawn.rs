@@ -23,9 +23,10 @@ pub fn get_pawn_attacks(side: Side, square: u8) -> u64 {
 pub fn get_pawn_attacks_table() -> [[u64; 64]; 2] {
     let mut table = [[0; 64]; 2];
 
-    for square in 0..64 {
-        table[Side::White as usize][square] = get_pawn_attacks(Side::White, square as u8);
-        table[Side::Black as usize][square] = get_pawn_attacks(Side::Black, square as u8);
+    for (side, attacks) in [Side::White, Side::Black].iter().zip(table.iter_mut()) {
+        for (square, board) in attacks.iter_mut().enumerate() {
+            *board = get_pawn_attacks(*side, square as u8);
+        }
     }
 
     table
@@ -1,29 +1,45 @@
-use crate::bitboard::set_bit;
+use crate::bitboard::{del_bit, get_bit, set_bit};
 use crate::defs::{Bitboard, CastlingRights, Piece, Side, Square};
+use crate::movegen::STANDARD_CASTLING_ROOKS;
+use crate::zobrist::piece_key;
 use std::collections::HashMap;
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Position {
     pub bitboards: [u64; 15],
     pub en_passant_square: u8,
     pub castling_rights: CastlingRights,
+    // Squares of the rooks the castling rights belong to, ordered like the
+    // rights bits, which are not in the corners in Chess960 positions
+    pub castling_rooks: [u8; 4],
+    // Castling moves are encoded as the king capturing its own rook, as in
+    // Chess960 move notation
+    pub chess960: bool,
     pub side_to_move: Side,
     pub fifty_move_count: u16,
     pub halfmove_count: u16,
+    // Zobrist hash of the position, kept up to date when moves are made
+    pub key: u64,
 }
 
-impl Position {
-    pub fn default() -> Self {
+impl Default for Position {
+    fn default() -> Self {
         Self {
             bitboards: [0; 15],
             en_passant_square: Square::NoSquare as u8,
             castling_rights: 0b1111,
+            castling_rooks: STANDARD_CASTLING_ROOKS,
+            chess960: false,
             side_to_move: Side::White,
             fifty_move_count: 0,
             halfmove_count: 2,
+            key: 0,
         }
+        .with_key()
     }
+}
 
+impl Position {
     pub fn from_position(position: HashMap<Square, Piece>) -> Self {
         let mut bitboards = [0; 15];
 
@@ -42,9 +58,53 @@ impl Position {
             bitboards,
             en_passant_square: Square::NoSquare as u8,
             castling_rights: 0b1111,
+            castling_rooks: STANDARD_CASTLING_ROOKS,
+            chess960: false,
             side_to_move: Side::White,
             fifty_move_count: 0,
             halfmove_count: 2,
+            key: 0,
+        }
+        .with_key()
+    }
+
+    // Sets the hash key computed from scratch
+    pub(crate) fn with_key(mut self) -> Self {
+        self.key = self.compute_key();
+        self
+    }
+
+    // Returns the bitboard index of a piece standing on the square
+    pub fn piece_at(&self, square: u8) -> Option<usize> {
+        if get_bit(self.bitboards[Bitboard::ALL_PIECES], square) == 0 {
+            return None;
+        }
+
+        (0..12).find(|&piece| get_bit(self.bitboards[piece], square) != 0)
+    }
+
+    #[inline]
+    pub(crate) fn add_piece(&mut self, piece: usize, square: u8) {
+        self.key ^= piece_key(piece, square);
+        set_bit(&mut self.bitboards[piece], square);
+        set_bit(&mut self.bitboards[Self::get_side_index_from_bitboard(piece)], square);
+        set_bit(&mut self.bitboards[Bitboard::ALL_PIECES], square);
+    }
+
+    #[inline]
+    pub(crate) fn remove_piece(&mut self, piece: usize, square: u8) {
+        self.key ^= piece_key(piece, square);
+        del_bit(&mut self.bitboards[piece], square);
+        del_bit(&mut self.bitboards[Self::get_side_index_from_bitboard(piece)], square);
+        del_bit(&mut self.bitboards[Bitboard::ALL_PIECES], square);
+    }
+
+    #[inline]
+    fn get_side_index_from_bitboard(piece: usize) -> usize {
+        if piece < Bitboard::BLACK_KING {
+            Bitboard::WHITE_PIECES
+        } else {
+            Bitboard::BLACK_PIECES
         }
     }
 
@@ -79,6 +139,31 @@ mod tests {
         verify_pos_bitboard(&position, Bitboard::ALL_PIECES, vec![E1, A2, B2, E8]);
     }
 
+    #[test]
+    fn gets_piece_at_square() {
+        let position = Position::from_position(map! {
+            E1 => Piece::WhiteKing,
+            D7 => Piece::BlackPawn
+        });
+
+        assert_eq!(position.piece_at(E1 as u8), Some(Bitboard::WHITE_KING));
+        assert_eq!(position.piece_at(D7 as u8), Some(Bitboard::BLACK_PAWNS));
+        assert_eq!(position.piece_at(E4 as u8), None);
+    }
+
+    #[test]
+    fn adds_and_removes_pieces() {
+        let mut position = Position::from_position(map! { E1 => Piece::WhiteKing });
+
+        position.add_piece(Bitboard::BLACK_QUEENS, D8 as u8);
+        verify_pos_bitboard(&position, Bitboard::BLACK_PIECES, vec![D8]);
+        verify_pos_bitboard(&position, Bitboard::ALL_PIECES, vec![E1, D8]);
+
+        position.remove_piece(Bitboard::WHITE_KING, E1 as u8);
+        verify_pos_bitboard(&position, Bitboard::WHITE_PIECES, vec![]);
+        verify_pos_bitboard(&position, Bitboard::ALL_PIECES, vec![D8]);
+    }
+
     #[test]
     fn gets_side_index() {
         assert_eq!(Position::get_side_index(Piece::WhiteKing), Bitboard::WHITE_PIECES);
@@ -0,0 +1,48 @@
+// Deterministic pseudo random number generator based on the xorshift algorithm
+// Used to find magic numbers and to seed hash keys, so that they are
+// reproducible between runs
+//
+// https://www.chessprogramming.org/Xorshift
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        assert_ne!(seed, 0);
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(2685821657736338717)
+    }
+
+    // Returns a number with a low count of set bits, which makes
+    // a good magic number candidate
+    pub fn sparse_u64(&mut self) -> u64 {
+        self.next_u64() & self.next_u64() & self.next_u64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_deterministic() {
+        let (mut a, mut b) = (Random::new(42), Random::new(42));
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn generates_sparse_numbers() {
+        let mut random = Random::new(42);
+        let total: u32 = (0..100).map(|_| random.sparse_u64().count_ones()).sum();
+        assert!(total / 100 < 16);
+    }
+}
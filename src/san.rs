@@ -0,0 +1,312 @@
+use crate::bitboard::coords;
+use crate::constants::{HUMAN_SQUARES, PIECE_CHARS};
+use crate::defs::{Bitboard, Square};
+use crate::fen::get_square_id;
+use crate::moves::Move;
+use crate::position::Position;
+use std::{error, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SanError {
+    InvalidSyntax(String),
+    IllegalMove(String),
+    AmbiguousMove(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::InvalidSyntax(san) => write!(f, "invalid move syntax \"{}\"", san),
+            SanError::IllegalMove(san) => write!(f, "illegal move \"{}\"", san),
+            SanError::AmbiguousMove(san) => write!(f, "ambiguous move \"{}\"", san),
+        }
+    }
+}
+
+impl error::Error for SanError {}
+
+const FILES: &str = "abcdefgh";
+const RANKS: &str = "87654321";
+
+impl Position {
+    // Parses a move in Standard Algebraic Notation (like "Nbd7" or "e8=Q+"),
+    // accepting check, mate and annotation suffixes as well as "e.p." markers
+    //
+    // https://www.chessprogramming.org/Algebraic_Chess_Notation#Standard_Algebraic_Notation_.28SAN.29
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+        let invalid = || SanError::InvalidSyntax(san.to_string());
+
+        let trimmed = san.trim().trim_end_matches(|c| "+#!?".contains(c));
+        let trimmed = trimmed.trim_end_matches("e.p.").trim_end_matches("ep").trim_end();
+
+        let candidates: Vec<Move> = match trimmed {
+            "O-O" | "0-0" => self.castling_moves(true),
+            "O-O-O" | "0-0-0" => self.castling_moves(false),
+            _ => {
+                let pattern = SanPattern::parse(trimmed).ok_or_else(invalid)?;
+                self.legal_moves().into_iter().filter(|&m| pattern.matches(m)).collect()
+            }
+        };
+
+        match candidates.len() {
+            0 => Err(SanError::IllegalMove(san.to_string())),
+            1 => Ok(candidates[0]),
+            _ => Err(SanError::AmbiguousMove(san.to_string())),
+        }
+    }
+
+    // Formats a legal move in Standard Algebraic Notation, using the minimal
+    // disambiguation and a check or mate suffix
+    pub fn to_san(&self, m: Move) -> String {
+        let mut san = String::new();
+        let piece = m.piece() % 6;
+        let target = HUMAN_SQUARES[m.target() as usize];
+
+        if m.is_castling() {
+            // In Chess960 the target is the rook square, still on the king's wing
+            san.push_str(if m.target() > m.source() { "O-O" } else { "O-O-O" });
+        } else if piece == Bitboard::WHITE_PAWNS {
+            if m.is_capture() {
+                san.push(file_char(m.source()));
+                san.push('x');
+            }
+            san.push_str(target);
+            if let Some(promoted) = m.promoted() {
+                san.push('=');
+                san.push(PIECE_CHARS[promoted % 6]);
+            }
+        } else {
+            san.push(PIECE_CHARS[piece]);
+            san.push_str(&self.disambiguation(m));
+            if m.is_capture() {
+                san.push('x');
+            }
+            san.push_str(target);
+        }
+
+        let mut after = self.clone();
+        after.make_move(m);
+        if after.in_check() {
+            san.push(if after.legal_moves().is_empty() { '#' } else { '+' });
+        }
+
+        san
+    }
+
+    fn castling_moves(&self, kingside: bool) -> Vec<Move> {
+        self.legal_moves()
+            .into_iter()
+            .filter(|m| m.is_castling() && (m.target() > m.source()) == kingside)
+            .collect()
+    }
+
+    // Returns the source file, rank or square needed to tell the move apart
+    // from moves of other pieces of the same type to the same square
+    fn disambiguation(&self, m: Move) -> String {
+        let others: Vec<Move> = self
+            .legal_moves()
+            .into_iter()
+            .filter(|other| {
+                other.piece() == m.piece() && other.target() == m.target() && *other != m
+            })
+            .collect();
+
+        let (rank, file) = coords(m.source());
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|other| coords(other.source()).1 != file) {
+            file_char(m.source()).to_string()
+        } else if others.iter().all(|other| coords(other.source()).0 != rank) {
+            rank_char(m.source()).to_string()
+        } else {
+            HUMAN_SQUARES[m.source() as usize].to_string()
+        }
+    }
+}
+
+fn file_char(square: u8) -> char {
+    FILES.as_bytes()[coords(square).1 as usize] as char
+}
+
+fn rank_char(square: u8) -> char {
+    RANKS.as_bytes()[coords(square).0 as usize] as char
+}
+
+// Parsed parts of a non-castling SAN move
+struct SanPattern {
+    // White piece bitboard index
+    piece: usize,
+    target: u8,
+    source_file: Option<u8>,
+    source_rank: Option<u8>,
+    promoted: Option<usize>,
+}
+
+impl SanPattern {
+    fn parse(san: &str) -> Option<Self> {
+        let mut chars: Vec<char> =
+            san.chars().filter(|&c| c != 'x' && c != '-' && c != '=').collect();
+
+        let piece = match chars.first() {
+            Some(&c) if "KQRBN".contains(c) => {
+                chars.remove(0);
+                piece_index(c)?
+            }
+            _ => Bitboard::WHITE_PAWNS,
+        };
+
+        let promoted = match chars.last() {
+            Some(&c) if piece == Bitboard::WHITE_PAWNS && "QRBNqrbn".contains(c) => {
+                chars.pop();
+                Some(piece_index(c.to_ascii_uppercase())?)
+            }
+            _ => None,
+        };
+
+        if chars.len() < 2 || chars.len() > 4 {
+            return None;
+        }
+
+        let target_str: String = chars.split_off(chars.len() - 2).into_iter().collect();
+        let target = get_square_id(&target_str);
+        if target == Square::NoSquare as u8 || target_str.chars().any(|c| c.is_uppercase()) {
+            return None;
+        }
+
+        let (mut source_file, mut source_rank) = (None, None);
+        for c in chars {
+            match (FILES.find(c), RANKS.find(c)) {
+                (Some(file), _) if source_file.is_none() => source_file = Some(file as u8),
+                (_, Some(rank)) if source_rank.is_none() => source_rank = Some(rank as u8),
+                _ => return None,
+            }
+        }
+
+        Some(Self { piece, target, source_file, source_rank, promoted })
+    }
+
+    fn matches(&self, m: Move) -> bool {
+        let (rank, file) = coords(m.source());
+
+        m.piece() % 6 == self.piece
+            && m.target() == self.target
+            && !m.is_castling()
+            && m.promoted().map(|promoted| promoted % 6) == self.promoted
+            && self.source_file.is_none_or(|source_file| source_file == file)
+            && self.source_rank.is_none_or(|source_rank| source_rank == rank)
+    }
+}
+
+fn piece_index(c: char) -> Option<usize> {
+    PIECE_CHARS[0..6].iter().position(|&piece| piece == c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::STARTING_FEN;
+
+    fn from_fen(fen: &str) -> Position {
+        Position::from_fen(fen).unwrap()
+    }
+
+    fn san_round_trip(position: &Position, san: &str) -> String {
+        position.to_san(position.parse_san(san).unwrap())
+    }
+
+    #[test]
+    fn parses_simple_moves() {
+        let position = from_fen(STARTING_FEN);
+
+        assert_eq!(position.parse_san("e4").unwrap().to_string(), "e2e4");
+        assert_eq!(position.parse_san("Nf3").unwrap().to_string(), "g1f3");
+        assert_eq!(position.parse_san("Ng1-f3").unwrap().to_string(), "g1f3");
+        assert_eq!(position.parse_san("Nc3!?").unwrap().to_string(), "b1c3");
+    }
+
+    #[test]
+    fn parses_disambiguated_moves() {
+        let position = from_fen("r3k2r/pp1n1ppp/8/1N6/8/8/PP1N1PPP/R3K2R b KQkq - 0 1");
+        assert_eq!(position.parse_san("Nb6").unwrap().to_string(), "d7b6");
+
+        let position = from_fen("4k3/8/8/8/8/8/3N1N2/4K3 w - - 0 1");
+        assert_eq!(position.parse_san("Nde4").unwrap().to_string(), "d2e4");
+        assert_eq!(position.parse_san("Nfe4").unwrap().to_string(), "f2e4");
+        assert_eq!(position.parse_san("Ne4"), Err(SanError::AmbiguousMove("Ne4".to_string())));
+
+        let position = from_fen("4k3/8/R7/8/8/8/8/R3K3 w - - 0 1");
+        assert_eq!(position.parse_san("R1a3").unwrap().to_string(), "a1a3");
+        assert_eq!(position.parse_san("R6a3").unwrap().to_string(), "a6a3");
+    }
+
+    #[test]
+    fn parses_special_moves() {
+        let en_passant = from_fen("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 2");
+        assert!(en_passant.parse_san("dxe6 e.p.").unwrap().is_en_passant());
+        assert!(en_passant.parse_san("dxe6").unwrap().is_en_passant());
+
+        let castling = from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+        assert_eq!(castling.parse_san("O-O-O").unwrap().to_string(), "e8c8");
+        assert_eq!(castling.parse_san("0-0").unwrap().to_string(), "e8g8");
+
+        let chess960 = from_fen("1r4kr/8/8/8/8/8/8/1R4KR w HBhb - 0 1");
+        assert_eq!(chess960.parse_san("O-O").unwrap().to_string(), "g1h1");
+        assert_eq!(chess960.to_san(chess960.parse_san("O-O-O").unwrap()), "O-O-O");
+
+        let promotion = from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(promotion.parse_san("a8=Q+").unwrap().to_string(), "a7a8q");
+        assert_eq!(promotion.parse_san("axb8N").unwrap().to_string(), "a7b8n");
+    }
+
+    #[test]
+    fn rejects_invalid_moves() {
+        let position = from_fen(STARTING_FEN);
+
+        assert_eq!(position.parse_san("e5"), Err(SanError::IllegalMove("e5".to_string())));
+        assert_eq!(position.parse_san("O-O"), Err(SanError::IllegalMove("O-O".to_string())));
+        assert_eq!(position.parse_san("Ze4"), Err(SanError::InvalidSyntax("Ze4".to_string())));
+        assert_eq!(position.parse_san("e9"), Err(SanError::InvalidSyntax("e9".to_string())));
+        assert_eq!(position.parse_san(""), Err(SanError::InvalidSyntax("".to_string())));
+        assert_eq!(position.parse_san("e8=K"), Err(SanError::InvalidSyntax("e8=K".to_string())));
+    }
+
+    #[test]
+    fn formats_moves() {
+        let position = from_fen(STARTING_FEN);
+        assert_eq!(san_round_trip(&position, "e4"), "e4");
+        assert_eq!(san_round_trip(&position, "Ng1-f3"), "Nf3");
+
+        let position =
+            from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        assert_eq!(san_round_trip(&position, "dxe6"), "dxe6");
+        assert_eq!(san_round_trip(&position, "Qxf6"), "Qxf6");
+        assert_eq!(san_round_trip(&position, "O-O-O"), "O-O-O");
+        assert_eq!(san_round_trip(&position, "Nxf7"), "Nxf7");
+    }
+
+    #[test]
+    fn formats_minimal_disambiguation() {
+        let position = from_fen("4k3/8/8/8/8/8/3N1N2/4K3 w - - 0 1");
+        assert_eq!(san_round_trip(&position, "Nde4"), "Nde4");
+        assert_eq!(san_round_trip(&position, "Nb3"), "Nb3");
+
+        let position = from_fen("4k3/8/R7/8/8/8/8/R3K3 w - - 0 1");
+        assert_eq!(san_round_trip(&position, "R1a3"), "R1a3");
+
+        let position = from_fen("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1");
+        assert_eq!(san_round_trip(&position, "Qa1b2"), "Qa1b2");
+        assert_eq!(san_round_trip(&position, "Qa3b2"), "Q3b2");
+        assert_eq!(san_round_trip(&position, "Qcb2"), "Qcb2");
+        assert_eq!(position.parse_san("Qb2"), Err(SanError::AmbiguousMove("Qb2".to_string())));
+    }
+
+    #[test]
+    fn formats_check_and_mate() {
+        let position = from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert_eq!(san_round_trip(&position, "Ra8"), "Ra8#");
+
+        let position = from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(san_round_trip(&position, "a8=Q+"), "a8=Q+");
+        assert_eq!(san_round_trip(&position, "a8=N"), "a8=N");
+    }
+}
@@ -0,0 +1,811 @@
+use crate::defs::Bitboard;
+use crate::eval::evaluate;
+use crate::moves::Move;
+use crate::position::Position;
+use crate::time::{Clock, TimeManager, DEFAULT_MOVE_OVERHEAD};
+use crate::tt::{Bound, TranspositionTable};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const MAX_PLY: usize = 64;
+pub const MAX_THREADS: usize = 256;
+pub const MAX_MULTIPV: usize = 256;
+pub const MATE: i32 = 32000;
+pub const INFINITY: i32 = 32001;
+// Scores beyond the bound mean a forced mate
+pub(crate) const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+
+// Nodes counted by a thread before adding them to the shared count
+const NODES_BATCH: u64 = 1024;
+
+// Piece values in centipawns, indexed by white piece bitboard index
+const PIECE_VALUES: [i32; 6] = [0, 900, 500, 330, 320, 100];
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    // Clock of the side to move, used when there is no fixed movetime
+    pub clock: Option<Clock>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    // Centipawns from the side to move's perspective
+    pub score: i32,
+    pub depth: u32,
+    pub pv: Vec<Move>,
+    // Rank of the line, starting from 1
+    pub multipv: usize,
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+impl SearchResult {
+    // Returns the number of moves until mate, negative when the side
+    // to move is getting mated
+    pub fn mate_in(&self) -> Option<i32> {
+        if self.score > MATE_BOUND {
+            Some((MATE - self.score + 1) / 2)
+        } else if self.score < -MATE_BOUND {
+            Some(-(MATE + self.score) / 2)
+        } else {
+            None
+        }
+    }
+}
+
+// Searches the position for the best move within the limits
+pub fn search(position: &Position, limits: SearchLimits) -> SearchResult {
+    Search::new(position, limits).run(|_| ())
+}
+
+// Searches the position for the given number of best lines, ranked from the best
+pub fn search_multipv(
+    position: &Position,
+    lines: usize,
+    limits: SearchLimits,
+) -> Vec<SearchResult> {
+    Search::new(position, limits).with_multipv(lines).run_multipv(|_| ())
+}
+
+// Iterative deepening negamax alpha-beta search
+//
+// https://www.chessprogramming.org/Alpha-Beta
+pub struct Search {
+    position: Position,
+    limits: SearchLimits,
+    stop: Arc<AtomicBool>,
+    // Set while searching on the opponent's time, when the time limits do not apply
+    ponder: Arc<AtomicBool>,
+    // Created when the search runs, unless a shared one was set
+    tt: Option<Arc<TranspositionTable>>,
+    // Keys of the positions preceding the current one, used to detect repetitions
+    keys: Vec<u64>,
+    // Number of threads searching the position, including this one
+    threads: usize,
+    // Number of best lines to search
+    multipv: usize,
+    // Root moves skipped while searching the lines after the first one
+    excluded: Vec<Move>,
+    move_overhead: Duration,
+    time: Option<TimeManager>,
+    start: Instant,
+    // Moment the engine's clock started running, which is the ponder hit when pondering
+    clock_start: Instant,
+    nodes: u64,
+    // Nodes searched by all threads, updated in batches
+    shared_nodes: Arc<AtomicU64>,
+    stopped: bool,
+    pv: Vec<Vec<Move>>,
+    previous_pv: Vec<Move>,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+}
+
+impl Search {
+    pub fn new(position: &Position, limits: SearchLimits) -> Self {
+        Self {
+            position: position.clone(),
+            limits,
+            stop: Arc::new(AtomicBool::new(false)),
+            ponder: Arc::new(AtomicBool::new(false)),
+            tt: None,
+            keys: Vec::new(),
+            threads: 1,
+            multipv: 1,
+            excluded: Vec::new(),
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            time: None,
+            start: Instant::now(),
+            clock_start: Instant::now(),
+            nodes: 0,
+            shared_nodes: Arc::new(AtomicU64::new(0)),
+            stopped: false,
+            pv: vec![Vec::new(); MAX_PLY + 1],
+            previous_pv: Vec::new(),
+            killers: [[None; 2]; MAX_PLY],
+        }
+    }
+
+    // Sets a flag which aborts the search from another thread
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    // Sets a flag which keeps the search going until it is cleared by a ponder hit,
+    // after which the time limits apply from the moment of the hit
+    //
+    // https://www.chessprogramming.org/Pondering
+    pub fn with_ponder(mut self, ponder: Arc<AtomicBool>) -> Self {
+        self.ponder = ponder;
+        self
+    }
+
+    // Sets the transposition table, which may be shared with other searches
+    pub fn with_tt(mut self, tt: Arc<TranspositionTable>) -> Self {
+        self.tt = Some(tt);
+        self
+    }
+
+    // Sets keys of the positions played before the searched one
+    pub fn with_history(mut self, keys: Vec<u64>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    // Sets the number of threads, which search the position in parallel
+    // sharing the transposition table
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.clamp(1, MAX_THREADS);
+        self
+    }
+
+    // Sets the number of best lines to search, the best move comes from the first one
+    pub fn with_multipv(mut self, lines: usize) -> Self {
+        self.multipv = lines.clamp(1, MAX_MULTIPV);
+        self
+    }
+
+    // Sets the time reserved for communication delays on every move
+    pub fn with_move_overhead(mut self, move_overhead: Duration) -> Self {
+        self.move_overhead = move_overhead;
+        self
+    }
+
+    // Runs iterative deepening, reporting the result of every completed iteration.
+    // Additional threads run their own searches until this one finishes, only
+    // helping through the transposition table (Lazy SMP)
+    //
+    // https://www.chessprogramming.org/Lazy_SMP
+    pub fn run(&mut self, report: impl FnMut(&SearchResult)) -> SearchResult {
+        self.run_multipv(report).swap_remove(0)
+    }
+
+    // Runs the search like run, returning all the searched lines ranked from the best
+    pub fn run_multipv(&mut self, mut report: impl FnMut(&SearchResult)) -> Vec<SearchResult> {
+        self.start = Instant::now();
+        self.clock_start = self.start;
+        self.tt.get_or_insert_with(Default::default).new_search();
+        self.time = self.limits.movetime.map(TimeManager::fixed).or_else(|| {
+            let halfmove_count = self.position.halfmove_count;
+            self.limits
+                .clock
+                .map(|clock| TimeManager::new(clock, halfmove_count, self.move_overhead))
+        });
+
+        let legal_moves = self.position.legal_moves();
+        if legal_moves.is_empty() {
+            let score = if self.position.in_check() { -MATE } else { 0 };
+            return vec![SearchResult { score, multipv: 1, ..Default::default() }];
+        }
+        self.multipv = self.multipv.min(legal_moves.len());
+
+        let helpers_stop = Arc::new(AtomicBool::new(false));
+        let mut lines = thread::scope(|scope| {
+            for id in 1..self.threads {
+                let mut helper = self.helper(helpers_stop.clone());
+                // Half of the helpers start one iteration ahead
+                scope.spawn(move || {
+                    helper.iterate(1 + id as u32 % 2, None, |_| ());
+                    helper.flush_nodes();
+                });
+            }
+
+            let lines = self.iterate(1, legal_moves.first().copied(), &mut report);
+            helpers_stop.store(true, Ordering::Relaxed);
+            lines
+        });
+
+        self.flush_nodes();
+        for line in &mut lines {
+            line.nodes = self.shared_nodes.load(Ordering::Relaxed);
+            line.time = self.start.elapsed();
+        }
+        lines
+    }
+
+    // Creates a search of the same position for a helper thread, which
+    // runs without limits until stopped
+    fn helper(&self, stop: Arc<AtomicBool>) -> Self {
+        Self {
+            stop,
+            tt: self.tt.clone(),
+            keys: self.keys.clone(),
+            shared_nodes: self.shared_nodes.clone(),
+            ..Self::new(&self.position, SearchLimits::default())
+        }
+    }
+
+    // Deepens the search of every line, one iteration at a time. Each line
+    // searches the root without the moves of the better lines before it
+    //
+    // https://www.chessprogramming.org/Multiple_PVs
+    fn iterate(
+        &mut self,
+        start_depth: u32,
+        best_move: Option<Move>,
+        mut report: impl FnMut(&SearchResult),
+    ) -> Vec<SearchResult> {
+        let mut lines = vec![SearchResult { best_move, multipv: 1, ..Default::default() }];
+
+        let max_depth = self.limits.depth.unwrap_or(MAX_PLY as u32).min(MAX_PLY as u32 - 1);
+        'deepening: for depth in start_depth..=max_depth {
+            let mut current: Vec<SearchResult> = Vec::new();
+            self.excluded.clear();
+
+            while current.len() < self.multipv {
+                self.previous_pv =
+                    lines.get(current.len()).map(|line| line.pv.clone()).unwrap_or_default();
+                let score = self.negamax(-INFINITY, INFINITY, depth, 0);
+
+                if self.stopped {
+                    break 'deepening;
+                }
+
+                self.excluded.extend(self.pv[0].first());
+                current.push(SearchResult {
+                    best_move: self.pv[0].first().copied(),
+                    score,
+                    depth,
+                    pv: self.pv[0].clone(),
+                    ..Default::default()
+                });
+            }
+
+            current.sort_by_key(|line| -line.score);
+            for (index, line) in current.iter_mut().enumerate() {
+                line.multipv = index + 1;
+                line.nodes = self.total_nodes();
+                line.time = self.start.elapsed();
+                report(line);
+            }
+            lines = current;
+
+            let elapsed = self.clock_elapsed();
+            if let Some(time) = self.time.as_mut() {
+                time.update(lines[0].best_move, lines[0].score);
+                if elapsed.is_some_and(|elapsed| !time.can_continue(elapsed)) {
+                    break;
+                }
+            }
+        }
+
+        lines
+    }
+
+    // Returns the time used on the engine's clock, or None while pondering
+    fn clock_elapsed(&mut self) -> Option<Duration> {
+        if self.ponder.load(Ordering::Relaxed) {
+            self.clock_start = Instant::now();
+            return None;
+        }
+        Some(self.clock_start.elapsed())
+    }
+
+    fn tt(&self) -> &TranspositionTable {
+        self.tt.as_ref().expect("transposition table is created when the search runs")
+    }
+
+    // Nodes searched so far by all threads, exact for a single thread
+    fn total_nodes(&self) -> u64 {
+        self.shared_nodes.load(Ordering::Relaxed) + self.nodes % NODES_BATCH
+    }
+
+    // Adds the nodes which were not counted in a full batch, once the thread finished
+    fn flush_nodes(&self) {
+        self.shared_nodes.fetch_add(self.nodes % NODES_BATCH, Ordering::Relaxed);
+    }
+
+    fn negamax(&mut self, mut alpha: i32, beta: i32, mut depth: u32, ply: usize) -> i32 {
+        self.pv[ply].clear();
+
+        if ply > 0 && self.is_draw() {
+            return 0;
+        }
+        if ply >= MAX_PLY - 1 {
+            return evaluate(&self.position);
+        }
+
+        // Check extension
+        let in_check = self.position.in_check();
+        if in_check {
+            depth += 1;
+        }
+
+        if depth == 0 {
+            return self.quiescence(alpha, beta, ply);
+        }
+
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        let entry = self.tt().probe(self.position.key, ply);
+        let tt_move = entry.and_then(|entry| entry.best_move);
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if cutoff {
+                if let Some(m) = tt_move.filter(|_| entry.bound == Bound::Exact) {
+                    self.pv[ply].push(m);
+                }
+                return entry.score.clamp(alpha, beta);
+            }
+        }
+
+        let original_alpha = alpha;
+        // The root result of a line without the better lines' moves must not replace
+        // the best move in the table, which orders the first line of the next iteration
+        let store = ply > 0 || self.excluded.is_empty();
+        let mut moves = self.position.pseudo_legal_moves();
+        self.order_moves(&mut moves, ply, tt_move);
+
+        let mut legal_moves = 0;
+        for m in moves {
+            if !self.position.is_legal(m) || (ply == 0 && self.excluded.contains(&m)) {
+                continue;
+            }
+            legal_moves += 1;
+
+            let score = -self.search_move(m, -beta, -alpha, depth - 1, ply);
+            if self.stopped {
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                self.update_pv(ply, m);
+
+                if score >= beta {
+                    if !m.is_capture() {
+                        self.store_killer(ply, m);
+                    }
+                    if store {
+                        self.tt().store(self.position.key, Some(m), beta, depth, Bound::Lower, ply);
+                    }
+                    return beta;
+                }
+            }
+        }
+
+        if legal_moves == 0 {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+
+        let (best_move, bound) = match alpha > original_alpha {
+            true => (self.pv[ply].first().copied(), Bound::Exact),
+            false => (None, Bound::Upper),
+        };
+        if store {
+            self.tt().store(self.position.key, best_move, alpha, depth, bound, ply);
+        }
+
+        alpha
+    }
+
+    // Searches captures only, until the position is quiet, to avoid
+    // misjudging positions in the middle of an exchange
+    //
+    // https://www.chessprogramming.org/Quiescence_Search
+    fn quiescence(&mut self, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.pv[ply].clear();
+
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        let stand_pat = evaluate(&self.position);
+        if ply >= MAX_PLY - 1 || stand_pat >= beta {
+            return stand_pat.min(beta);
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves: Vec<Move> = self
+            .position
+            .pseudo_legal_moves()
+            .into_iter()
+            .filter(|m| m.is_capture() || m.promoted().is_some())
+            .collect();
+        self.order_moves(&mut moves, ply, None);
+
+        for m in moves {
+            if !self.position.is_legal(m) {
+                continue;
+            }
+
+            self.keys.push(self.position.key);
+            let undo = self.position.make_move(m);
+            let score = -self.quiescence(-beta, -alpha, ply + 1);
+            self.position.unmake_move(m, undo);
+            self.keys.pop();
+
+            if self.stopped {
+                return 0;
+            }
+            if score > alpha {
+                alpha = score;
+                if score >= beta {
+                    return beta;
+                }
+            }
+        }
+
+        alpha
+    }
+
+    fn search_move(&mut self, m: Move, alpha: i32, beta: i32, depth: u32, ply: usize) -> i32 {
+        self.keys.push(self.position.key);
+        let undo = self.position.make_move(m);
+        let score = self.negamax(alpha, beta, depth, ply + 1);
+        self.position.unmake_move(m, undo);
+        self.keys.pop();
+
+        score
+    }
+
+    // Detects the fifty-move rule and repetitions since the last irreversible move
+    fn is_draw(&self) -> bool {
+        let position = &self.position;
+
+        position.fifty_move_count >= 100
+            || self
+                .keys
+                .iter()
+                .rev()
+                .take(position.fifty_move_count as usize)
+                .skip(1)
+                .step_by(2)
+                .any(|&key| key == position.key)
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.nodes.is_multiple_of(NODES_BATCH) {
+            self.shared_nodes.fetch_add(NODES_BATCH, Ordering::Relaxed);
+            if let Some(elapsed) = self.clock_elapsed() {
+                self.stopped |= self.time.as_ref().is_some_and(|time| time.is_out_of_time(elapsed));
+            }
+        }
+        self.stopped |= self.stop.load(Ordering::Relaxed);
+        // Only the main thread has a node limit, which counts the helpers' nodes too
+        if let Some(nodes) = self.limits.nodes {
+            self.stopped |= self.total_nodes() >= nodes;
+        }
+
+        self.stopped
+    }
+
+    fn update_pv(&mut self, ply: usize, m: Move) {
+        let (current, next) = self.pv.split_at_mut(ply + 1);
+        current[ply].clear();
+        current[ply].push(m);
+        current[ply].extend_from_slice(&next[0]);
+    }
+
+    fn store_killer(&mut self, ply: usize, m: Move) {
+        if self.killers[ply][0] != Some(m) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(m);
+        }
+    }
+
+    // Orders moves so that the likely best ones are searched first:
+    // the transposition table move, the previous principal variation,
+    // captures by MVV-LVA, promotions and killer moves
+    //
+    // https://www.chessprogramming.org/Move_Ordering
+    fn order_moves(&self, moves: &mut [Move], ply: usize, tt_move: Option<Move>) {
+        moves.sort_by_cached_key(|&m| -self.score_move(m, ply, tt_move));
+    }
+
+    fn score_move(&self, m: Move, ply: usize, tt_move: Option<Move>) -> i32 {
+        if tt_move == Some(m) {
+            40000
+        } else if self.previous_pv.get(ply) == Some(&m) {
+            30000
+        } else if m.is_capture() {
+            let victim = if m.is_en_passant() {
+                Bitboard::WHITE_PAWNS
+            } else {
+                self.position.piece_at(m.target()).map_or(Bitboard::WHITE_PAWNS, |piece| piece % 6)
+            };
+            let mvv_lva = PIECE_VALUES[victim] * 10 - PIECE_VALUES[m.piece() % 6] / 10;
+            // Captures losing material are tried after the killer moves
+            if self.position.see_ge(m, 0) {
+                20000 + mvv_lva
+            } else {
+                mvv_lva
+            }
+        } else if let Some(promoted) = m.promoted() {
+            15000 + PIECE_VALUES[promoted % 6]
+        } else if self.killers[ply][0] == Some(m) {
+            10000
+        } else if self.killers[ply][1] == Some(m) {
+            9000
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_depth(fen: &str, depth: u32) -> SearchResult {
+        search(
+            &Position::from_fen(fen).unwrap(),
+            SearchLimits { depth: Some(depth), ..Default::default() },
+        )
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let result = search_depth("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2);
+
+        assert_eq!(result.best_move.unwrap().to_string(), "a1a8");
+        assert_eq!(result.score, MATE - 1);
+        assert_eq!(result.mate_in(), Some(1));
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        let result = search_depth("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", 4);
+
+        assert_eq!(result.mate_in(), Some(2));
+        assert_eq!(result.pv.len(), 3);
+    }
+
+    #[test]
+    fn searches_multiple_lines() {
+        let position = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let lines =
+            search_multipv(&position, 4, SearchLimits { depth: Some(3), ..Default::default() });
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].best_move.unwrap().to_string(), "a1a8");
+        assert_eq!(lines[0].mate_in(), Some(1));
+        for (index, line) in lines.iter().enumerate() {
+            assert_eq!((line.multipv, line.depth), (index + 1, 3));
+            assert_eq!(line.pv.first(), line.best_move.as_ref());
+        }
+        assert!(lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        let moves: Vec<Move> = lines.iter().filter_map(|line| line.best_move).collect();
+        assert!(moves.iter().enumerate().all(|(index, m)| !moves[..index].contains(m)));
+    }
+
+    #[test]
+    fn keeps_best_move_first_with_multiple_lines() {
+        let position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let mut search =
+            Search::new(&position, SearchLimits { depth: Some(4), ..Default::default() })
+                .with_multipv(3);
+
+        let lines = search.run_multipv(|_| ());
+
+        // The next iteration of the first line starts from the root move in the table
+        let entry = search.tt().probe(position.key, 0).unwrap();
+        assert_eq!(entry.best_move, lines[0].best_move);
+    }
+
+    #[test]
+    fn limits_lines_to_legal_moves() {
+        let position = Position::from_fen("k7/8/1K6/8/8/8/8/7R b - - 0 1").unwrap();
+        let lines =
+            search_multipv(&position, 5, SearchLimits { depth: Some(2), ..Default::default() });
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].best_move.unwrap().to_string(), "a8b8");
+    }
+
+    #[test]
+    fn reports_getting_mated() {
+        let result = search_depth("6k1/5ppp/8/8/8/8/r7/1R4K1 b - - 0 1", 3);
+        assert_eq!(result.mate_in(), None);
+
+        let result = search_depth("k7/8/1K6/8/8/8/8/7R b - - 0 1", 3);
+        assert_eq!(result.best_move.unwrap().to_string(), "a8b8");
+        assert_eq!(result.mate_in(), Some(-1));
+    }
+
+    #[test]
+    fn captures_hanging_piece() {
+        let result = search_depth("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 3);
+
+        assert_eq!(result.best_move.unwrap().to_string(), "d2d5");
+        assert!(result.score > 400);
+    }
+
+    #[test]
+    fn avoids_defended_piece() {
+        let result = search_depth("4k3/2p5/3n4/8/8/8/3Q4/4K3 w - - 0 1", 3);
+        assert_ne!(result.best_move.unwrap().to_string(), "d2d6");
+    }
+
+    #[test]
+    fn handles_game_over() {
+        let mated = search_depth("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", 3);
+        assert_eq!((mated.best_move, mated.score, mated.mate_in()), (None, -MATE, Some(0)));
+
+        let stalemate = search_depth("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 3);
+        assert_eq!((stalemate.best_move, stalemate.score), (None, 0));
+    }
+
+    #[test]
+    fn returns_legal_principal_variation() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let result = search_depth(fen, 3);
+        let mut position = Position::from_fen(fen).unwrap();
+
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.best_move, result.pv.first().copied());
+        for m in &result.pv {
+            assert!(position.legal_moves().contains(m));
+            position.make_move(*m);
+        }
+    }
+
+    #[test]
+    fn reports_iterations() {
+        let position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let mut depths = Vec::new();
+
+        Search::new(&position, SearchLimits { depth: Some(3), ..Default::default() })
+            .run(|result| depths.push(result.depth));
+
+        assert_eq!(depths, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn respects_node_limit() {
+        let position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let result = search(&position, SearchLimits { nodes: Some(5000), ..Default::default() });
+
+        assert!(result.nodes <= 5001);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn stops_on_flag() {
+        let position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let stop = Arc::new(AtomicBool::new(true));
+
+        let result = Search::new(&position, SearchLimits::default()).with_stop(stop).run(|_| ());
+
+        assert!(result.best_move.is_some());
+        assert!(result.depth < 3);
+    }
+
+    #[test]
+    fn stops_within_clock_budget() {
+        let position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let clock = Clock { time: Duration::from_secs(2), ..Default::default() };
+        let result = search(&position, SearchLimits { clock: Some(clock), ..Default::default() });
+
+        // The hard limit is an eighth of the clock
+        assert!(result.time < Duration::from_millis(400), "{:?}", result.time);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn starts_clock_on_ponderhit() {
+        let position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let clock = Clock { time: Duration::from_millis(200), ..Default::default() };
+        let ponder = Arc::new(AtomicBool::new(true));
+        let mut search =
+            Search::new(&position, SearchLimits { clock: Some(clock), ..Default::default() })
+                .with_ponder(ponder.clone());
+
+        let pondering = thread::spawn(move || search.run(|_| ()));
+        thread::sleep(Duration::from_millis(300));
+        assert!(!pondering.is_finished());
+
+        ponder.store(false, Ordering::Relaxed);
+        let result = pondering.join().unwrap();
+        assert!(result.time >= Duration::from_millis(300));
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn creates_tt_only_when_needed() {
+        let position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let limits = SearchLimits { depth: Some(2), ..Default::default() };
+
+        let mut search = Search::new(&position, limits.clone());
+        assert!(search.tt.is_none());
+        search.run(|_| ());
+        assert!(search.tt.is_some());
+
+        let tt = Arc::new(TranspositionTable::new(1));
+        let mut search = Search::new(&position, limits).with_tt(tt.clone());
+        search.run(|_| ());
+        assert!(Arc::ptr_eq(search.tt.as_ref().unwrap(), &tt));
+    }
+
+    #[test]
+    fn limits_nodes_of_all_threads() {
+        let position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let limits = SearchLimits { nodes: Some(50000), ..Default::default() };
+        let result = Search::new(&position, limits).with_threads(4).run(|_| ());
+
+        // Helpers may finish their current batch after the main thread stops
+        assert!(result.nodes <= 50000 + 4 * NODES_BATCH, "{}", result.nodes);
+    }
+
+    #[test]
+    fn searches_deterministically_with_one_thread() {
+        let position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let run = || {
+            let result = search(&position, SearchLimits { depth: Some(4), ..Default::default() });
+            (result.best_move, result.score, result.pv, result.nodes)
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn searches_with_threads() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let position = Position::from_fen(fen).unwrap();
+        let mut search =
+            Search::new(&position, SearchLimits { depth: Some(4), ..Default::default() })
+                .with_threads(4);
+        let result = search.run(|_| ());
+
+        assert_eq!(result.depth, 4);
+        assert!(position.legal_moves().contains(&result.best_move.unwrap()));
+        // Nodes of the helper threads are included
+        assert!(result.nodes > search.nodes);
+
+        let position = Position::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        let result = Search::new(&position, SearchLimits { depth: Some(4), ..Default::default() })
+            .with_threads(2)
+            .run(|_| ());
+        assert_eq!(result.mate_in(), Some(2));
+    }
+
+    #[test]
+    fn detects_repetitions() {
+        let mut position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        let mut keys = Vec::new();
+
+        for m in &["g1f3", "g8f6", "f3g1", "f6g8"] {
+            let m = *position.legal_moves().iter().find(|legal| legal.to_string() == *m).unwrap();
+            keys.push(position.key);
+            position.make_move(m);
+        }
+
+        assert!(Search::new(&position, SearchLimits::default()).with_history(keys).is_draw());
+        assert!(!Search::new(&position, SearchLimits::default()).is_draw());
+    }
+}
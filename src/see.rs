@@ -0,0 +1,159 @@
+use crate::bitboard::get_ls1b_index;
+use crate::constants::{RANK_1, RANK_8};
+use crate::defs::{Bitboard, Side};
+use crate::movegen::en_passant_victim;
+use crate::moves::Move;
+use crate::position::Position;
+
+// Piece values used for exchanges, indexed like bitboards of one side
+// (king, queen, rook, bishop, knight, pawn)
+pub const SEE_VALUES: [i32; 6] = [20000, 900, 500, 330, 320, 100];
+
+// Maximum number of captures in an exchange on a single square
+const MAX_EXCHANGE: usize = 32;
+
+impl Position {
+    // Static Exchange Evaluation: the material balance of the move followed
+    // by the best sequence of captures on its target square, where each side
+    // captures with its least valuable piece or stops when it is better off.
+    // Sliders behind the capturing pieces join the exchange, pins are ignored.
+    //
+    // https://www.chessprogramming.org/Static_Exchange_Evaluation
+    pub fn see(&self, m: Move) -> i32 {
+        if m.is_castling() {
+            return 0;
+        }
+
+        let (source, target) = (m.source(), m.target());
+        let mut occupancy = self.bitboards[Bitboard::ALL_PIECES] ^ (1 << source);
+        let mut gain = [0; MAX_EXCHANGE];
+
+        gain[0] = if m.is_en_passant() {
+            occupancy ^= 1 << en_passant_victim(self.side_to_move, target);
+            SEE_VALUES[Bitboard::WHITE_PAWNS]
+        } else {
+            self.piece_at(target).map_or(0, |piece| SEE_VALUES[piece % 6])
+        };
+
+        // Value of the piece standing on the target square after the capture
+        let mut on_target = SEE_VALUES[m.piece() % 6];
+        if let Some(promoted) = m.promoted() {
+            gain[0] += SEE_VALUES[promoted % 6] - SEE_VALUES[Bitboard::WHITE_PAWNS];
+            on_target = SEE_VALUES[promoted % 6];
+        }
+
+        let mut side = !self.side_to_move;
+        let mut depth = 0;
+        while depth + 1 < MAX_EXCHANGE {
+            let attackers = self.attackers_to(target, occupancy) & occupancy;
+            let (piece, square) = match self.least_valuable_attacker(attackers, side) {
+                Some(attacker) => attacker,
+                None => break,
+            };
+
+            depth += 1;
+            gain[depth] = on_target - gain[depth - 1];
+            on_target = SEE_VALUES[piece];
+
+            // Pawns reaching the last rank promote to queens
+            if piece == Bitboard::WHITE_PAWNS && (1 << target) & (RANK_1 | RANK_8) != 0 {
+                gain[depth] += SEE_VALUES[Bitboard::WHITE_QUEENS] - SEE_VALUES[piece];
+                on_target = SEE_VALUES[Bitboard::WHITE_QUEENS];
+            }
+
+            occupancy ^= 1 << square;
+            side = !side;
+        }
+
+        while depth > 0 {
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+            depth -= 1;
+        }
+
+        gain[0]
+    }
+
+    // Checks whether the static exchange evaluation of the move is at least
+    // the given threshold
+    pub fn see_ge(&self, m: Move, threshold: i32) -> bool {
+        self.see(m) >= threshold
+    }
+
+    // Returns the piece type (as a bitboard index of White pieces) and square
+    // of the least valuable attacker of the given side
+    fn least_valuable_attacker(&self, attackers: u64, side: Side) -> Option<(usize, u8)> {
+        (Bitboard::WHITE_KING..=Bitboard::WHITE_PAWNS).rev().find_map(|piece| {
+            let pieces = attackers & self.bitboards[piece + side.piece_offset()];
+            (pieces != 0).then(|| (piece, get_ls1b_index(pieces)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see(fen: &str, m: &str) -> i32 {
+        let position = Position::from_fen(fen).unwrap();
+        position.see(position.parse_uci_move(m).unwrap())
+    }
+
+    #[test]
+    fn evaluates_simple_captures() {
+        assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+        assert_eq!(see("4k3/8/3n4/4P3/8/8/8/4K3 w - - 0 1", "e5d6"), 320);
+        assert_eq!(see("3rk3/8/8/3p4/8/8/8/3RK3 w - - 0 1", "d1d5"), -400);
+        assert_eq!(see("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), -800);
+    }
+
+    #[test]
+    fn evaluates_exchange_sequences() {
+        assert_eq!(see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), -220);
+        assert_eq!(see("4k3/8/2n5/3p4/4P3/2N5/8/4K3 w - - 0 1", "e4d5"), 100);
+        assert_eq!(see("4k3/4r3/8/8/8/8/4R3/4R1K1 w - - 0 1", "e2e7"), 500);
+    }
+
+    #[test]
+    fn includes_x_ray_attackers() {
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+        assert_eq!(see("3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), -400);
+        assert_eq!(see("4k3/8/8/3p4/4B3/5Q2/8/4K3 w - - 0 1", "e4d5"), 100);
+        assert_eq!(see("4k3/8/5n2/3p4/4B3/5Q2/8/4K3 w - - 0 1", "e4d5"), 90);
+    }
+
+    #[test]
+    fn evaluates_en_passant() {
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+        assert_eq!(see("4k3/2p5/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 0);
+        // The captured pawn no longer blocks the rook behind it
+        assert_eq!(see("4k3/8/8/3pP3/8/8/7K/3r4 w - d6 0 1", "e5d6"), 0);
+    }
+
+    #[test]
+    fn evaluates_promotions() {
+        assert_eq!(see("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), 800);
+        assert_eq!(see("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8n"), 220);
+        assert_eq!(see("3rk3/4P3/8/8/8/8/8/K7 w - - 0 1", "e7d8q"), 400);
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/R3K3 w - - 0 1", "a7b8q"), 1300);
+        // Recapturing pawn promotes as well
+        assert_eq!(see("r3k3/1P6/8/8/8/8/8/4K3 b - - 0 1", "a8c8"), -1300);
+    }
+
+    #[test]
+    fn evaluates_quiet_moves() {
+        assert_eq!(see("4k3/8/8/8/8/2p5/8/1N2K3 w - - 0 1", "b1d2"), -220);
+        assert_eq!(see("4k3/8/8/8/8/2p5/8/1N5K w - - 0 1", "b1d2"), -320);
+        assert_eq!(see("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1", "b1c3"), 0);
+        assert_eq!(see("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1g1"), 0);
+    }
+
+    #[test]
+    fn compares_with_threshold() {
+        let position = Position::from_fen("3rk3/8/8/3p4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let m = position.parse_uci_move("d1d5").unwrap();
+
+        assert!(position.see_ge(m, -400));
+        assert!(!position.see_ge(m, -399));
+        assert!(!position.see_ge(m, 0));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::moves::Move;
+use std::time::Duration;
+
+pub const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+pub const MAX_MOVE_OVERHEAD: Duration = Duration::from_millis(5000);
+
+// Moves expected until the end of the game, when the time control does not tell
+const MAX_MOVES_TO_GO: u32 = 40;
+const MIN_MOVES_TO_GO: u32 = 20;
+// Multiple of the soft limit the search may take when the position is unclear
+const MAX_SOFT_SCALE: u32 = 3;
+// Share of the clock the search may take unless the time control is one move away
+const MAX_CLOCK_SHARE: u32 = 8;
+// Drop of the score between iterations which extends the search
+const FAIL_LOW_MARGIN: i32 = 30;
+
+// Remaining time of the side to move as given by "go"
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Clock {
+    pub time: Duration,
+    pub increment: Duration,
+    pub moves_to_go: Option<u32>,
+}
+
+// Decides how long to search: no new iteration is started after the soft
+// limit, which is extended while the best move keeps changing or the score
+// drops, and the search is aborted at the hard limit
+//
+// https://www.chessprogramming.org/Time_Management
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeManager {
+    soft: Duration,
+    hard: Duration,
+    // Extension of the soft limit based on the last iterations
+    scale: f64,
+    // Number of best move changes, halved with every iteration
+    best_move_changes: f64,
+    previous: Option<(Option<Move>, i32)>,
+}
+
+impl TimeManager {
+    // Allocates time for a move from the clock, spreading the remaining time
+    // over the moves to the next time control or to the expected end of the game.
+    // Communication delays are reserved for each of these moves
+    pub fn new(clock: Clock, halfmove_count: u16, move_overhead: Duration) -> Self {
+        let moves_to_go = match clock.moves_to_go {
+            Some(moves) => moves.clamp(1, MAX_MOVES_TO_GO),
+            // Fewer moves are expected to be left the longer the game lasts
+            None => {
+                (50 - (halfmove_count / 2).min(50) as u32).clamp(MIN_MOVES_TO_GO, MAX_MOVES_TO_GO)
+            }
+        };
+
+        let available = clock.time.saturating_sub(move_overhead);
+        let total = (clock.time + clock.increment * (moves_to_go - 1))
+            .saturating_sub(move_overhead * (moves_to_go + 1));
+        let soft = total / moves_to_go;
+        // The hard limit never takes more than a fraction of the clock,
+        // unless this is the last move before the time control
+        let max_share = if moves_to_go == 1 { available } else { available / MAX_CLOCK_SHARE };
+        let hard = (soft * MAX_SOFT_SCALE).min(max_share).max(Duration::from_millis(1));
+
+        Self { soft: soft.min(hard), hard, ..Self::fixed(hard) }
+    }
+
+    // Searches for exactly the given time
+    pub fn fixed(movetime: Duration) -> Self {
+        Self { soft: movetime, hard: movetime, scale: 1.0, best_move_changes: 0.0, previous: None }
+    }
+
+    pub fn soft_limit(&self) -> Duration {
+        self.soft.mul_f64(self.scale).min(self.hard)
+    }
+
+    pub fn hard_limit(&self) -> Duration {
+        self.hard
+    }
+
+    // Records the result of a completed iteration, extending the soft limit
+    // when the best move changed recently or the score dropped
+    pub fn update(&mut self, best_move: Option<Move>, score: i32) {
+        self.best_move_changes /= 2.0;
+        let mut fail_low = false;
+
+        if let Some((previous_move, previous_score)) = self.previous {
+            if previous_move != best_move {
+                self.best_move_changes += 1.0;
+            }
+            fail_low = score < previous_score - FAIL_LOW_MARGIN;
+        }
+
+        self.scale = (1.0 + self.best_move_changes / 2.0) * if fail_low { 1.5 } else { 1.0 };
+        self.previous = Some((best_move, score));
+    }
+
+    // Checks whether there is time to start another iteration
+    pub fn can_continue(&self, elapsed: Duration) -> bool {
+        elapsed < self.soft_limit()
+    }
+
+    // Checks whether the search has to be aborted
+    pub fn is_out_of_time(&self, elapsed: Duration) -> bool {
+        elapsed >= self.hard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::Bitboard;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn some_move(source: u8) -> Option<Move> {
+        Some(Move::new(source, 0, Bitboard::WHITE_ROOKS, None, 0))
+    }
+
+    // Plays moves against a fake clock, where every iteration takes three times
+    // longer than the previous one and the best move changes every third
+    // iteration. Returns the time left after each move
+    fn play_game(mut clock: Clock, moves: u16, lag: Duration) -> Vec<Duration> {
+        let (mut left, control) = (Vec::new(), clock.time);
+
+        for ply in (0..moves * 2).step_by(2) {
+            let mut time = TimeManager::new(clock, ply + 2, DEFAULT_MOVE_OVERHEAD);
+            let (mut elapsed, mut iteration) = (Duration::ZERO, ms(1));
+
+            for depth in 1.. {
+                if time.is_out_of_time(elapsed + iteration) {
+                    elapsed = time.hard_limit();
+                    break;
+                }
+                elapsed += iteration;
+                iteration *= 3;
+
+                time.update(some_move(depth as u8 / 3), -(depth % 4) * 20);
+                if !time.can_continue(elapsed) {
+                    break;
+                }
+            }
+
+            let used = elapsed + lag;
+            assert!(used < clock.time, "flagged on move {} with {:?} left", ply / 2, clock.time);
+            clock.time = clock.time - used + clock.increment;
+            if let Some(moves_to_go) = clock.moves_to_go.as_mut() {
+                *moves_to_go -= 1;
+                if *moves_to_go == 0 {
+                    *moves_to_go = 40;
+                    clock.time += control;
+                }
+            }
+            left.push(clock.time);
+        }
+
+        left
+    }
+
+    #[test]
+    fn allocates_time() {
+        let clock = Clock { time: ms(60000), ..Default::default() };
+        let time = TimeManager::new(clock, 2, DEFAULT_MOVE_OVERHEAD);
+        assert_eq!((time.soft_limit().as_millis(), time.hard_limit().as_millis()), (1448, 4346));
+
+        // Later in the game a larger share of the clock is used
+        let later = TimeManager::new(clock, 80, DEFAULT_MOVE_OVERHEAD);
+        assert!(later.soft_limit() > time.soft_limit());
+
+        let clock = Clock { time: ms(10000), increment: ms(1000), moves_to_go: Some(10) };
+        let time = TimeManager::new(clock, 2, DEFAULT_MOVE_OVERHEAD);
+        assert_eq!((time.soft_limit().as_millis(), time.hard_limit().as_millis()), (1243, 1243));
+
+        let clock = Clock { time: ms(10), increment: ms(1000), moves_to_go: None };
+        assert_eq!(TimeManager::new(clock, 2, DEFAULT_MOVE_OVERHEAD).hard_limit(), ms(1));
+    }
+
+    #[test]
+    fn uses_most_of_the_clock_before_time_control() {
+        let clock = Clock { time: ms(5000), increment: Duration::ZERO, moves_to_go: Some(1) };
+        let time = TimeManager::new(clock, 2, DEFAULT_MOVE_OVERHEAD);
+
+        assert_eq!((time.soft_limit(), time.hard_limit()), (ms(4900), ms(4950)));
+    }
+
+    #[test]
+    fn extends_on_instability_and_fail_lows() {
+        let clock = Clock { time: ms(60000), ..Default::default() };
+        let mut time = TimeManager::new(clock, 2, DEFAULT_MOVE_OVERHEAD);
+        let base = time.soft_limit();
+
+        time.update(some_move(1), 20);
+        time.update(some_move(1), 25);
+        assert_eq!(time.soft_limit(), base);
+
+        time.update(some_move(2), 25);
+        assert!(time.soft_limit() > base);
+        time.update(some_move(2), 25);
+        time.update(some_move(2), 25);
+        let settled = time.soft_limit();
+
+        time.update(some_move(2), -50);
+        assert!(time.soft_limit() > settled);
+        assert!(time.soft_limit() <= time.hard_limit());
+    }
+
+    #[test]
+    fn fixed_time_is_not_extended() {
+        let mut time = TimeManager::fixed(ms(300));
+        time.update(some_move(1), 0);
+        time.update(some_move(2), -500);
+
+        assert_eq!((time.soft_limit(), time.hard_limit()), (ms(300), ms(300)));
+        assert!(time.can_continue(ms(299)) && !time.can_continue(ms(300)));
+        assert!(time.is_out_of_time(ms(300)));
+    }
+
+    #[test]
+    fn does_not_flag_in_sudden_death() {
+        for millis in [5000, 10000, 60000, 300000] {
+            let clock = Clock { time: ms(millis), ..Default::default() };
+            let left = play_game(clock, 120, ms(10));
+
+            // Time for the communication delays is kept until the end of a long game
+            assert!(left[119] > ms(200), "{:?}", left);
+        }
+    }
+
+    #[test]
+    fn does_not_flag_with_increment_or_moves_to_go() {
+        let clock = Clock { time: ms(2000), increment: ms(100), moves_to_go: None };
+        let left = play_game(clock, 200, ms(40));
+        // The clock settles instead of running down
+        assert!(left.iter().all(|&time| time > ms(250)), "{:?}", left);
+
+        let clock = Clock { time: ms(30000), increment: Duration::ZERO, moves_to_go: Some(40) };
+        play_game(clock, 120, ms(40));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::moves::Move;
+use crate::search::MATE_BOUND;
+use std::mem;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 65536;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Bound {
+    // Score is exact, all moves were searched within the window
+    Exact,
+    // Score is at least this high, the search failed high
+    Lower,
+    // Score is at most this high, no move raised alpha
+    Upper,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TtEntry {
+    pub best_move: Option<Move>,
+    // Mate scores are relative to the probing ply
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+    // Search generation the entry was stored in
+    pub age: u8,
+}
+
+impl TtEntry {
+    // Packs the entry into 64 bits: move (24), score (16), depth (8),
+    // bound (2) and age (8), where a zero bound marks an empty slot
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+
+        self.best_move.map_or(0, |m| m.raw() as u64)
+            | (self.score as i16 as u16 as u64) << 24
+            | (self.depth.min(u8::MAX as u32) as u64) << 40
+            | bound << 48
+            | (self.age as u64) << 50
+    }
+
+    fn unpack(data: u64) -> Option<Self> {
+        let bound = match (data >> 48) & 3 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => return None,
+        };
+        let m = (data & 0xFFFFFF) as u32;
+
+        Some(Self {
+            best_move: (m != 0).then(|| Move::from_raw(m)),
+            score: (data >> 24) as u16 as i16 as i32,
+            depth: (data >> 40) as u8 as u32,
+            bound,
+            age: (data >> 50) as u8,
+        })
+    }
+}
+
+// Entry stored as its key XOR-ed with the data, so that an entry torn by
+// concurrent writes fails the key check instead of returning wrong data
+//
+// https://www.chessprogramming.org/Shared_Hash_Table#Lockless
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    fn load(&self) -> (u64, u64) {
+        let data = self.data.load(Ordering::Relaxed);
+        (self.key.load(Ordering::Relaxed) ^ data, data)
+    }
+
+    fn store(&self, key: u64, data: u64) {
+        self.key.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+// The first slot keeps the deepest entry, the second one is always replaced
+#[derive(Default)]
+struct Bucket {
+    slots: [Slot; 2],
+}
+
+// Transposition table shared by search threads, keyed by the Zobrist key
+//
+// https://www.chessprogramming.org/Transposition_Table
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    age: AtomicU8,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB)
+    }
+}
+
+impl TranspositionTable {
+    // Creates a table taking up the given number of megabytes
+    pub fn new(megabytes: usize) -> Self {
+        let count = (megabytes.clamp(1, MAX_HASH_MB) << 20) / mem::size_of::<Bucket>();
+
+        Self { buckets: (0..count).map(|_| Bucket::default()).collect(), age: AtomicU8::new(0) }
+    }
+
+    pub fn size_mb(&self) -> usize {
+        (self.buckets.len() * mem::size_of::<Bucket>()) >> 20
+    }
+
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flat_map(|bucket| &bucket.slots) {
+            slot.store(0, 0);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    // Starts a new search generation, so that entries of the previous
+    // searches are replaced first
+    pub fn new_search(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        let index = ((key as u128 * self.buckets.len() as u128) >> 64) as usize;
+        &self.buckets[index]
+    }
+
+    // Returns the entry of the position, with mate scores adjusted to
+    // be relative to the given ply
+    pub fn probe(&self, key: u64, ply: usize) -> Option<TtEntry> {
+        self.bucket(key).slots.iter().find_map(|slot| {
+            let (slot_key, data) = slot.load();
+            let mut entry = TtEntry::unpack(data).filter(|_| slot_key == key)?;
+            entry.score = score_from_tt(entry.score, ply);
+            Some(entry)
+        })
+    }
+
+    pub fn store(
+        &self,
+        key: u64,
+        best_move: Option<Move>,
+        score: i32,
+        depth: u32,
+        bound: Bound,
+        ply: usize,
+    ) {
+        let age = self.age.load(Ordering::Relaxed);
+        let bucket = self.bucket(key);
+
+        let (deep_key, deep_data) = bucket.slots[0].load();
+        let deep = TtEntry::unpack(deep_data);
+
+        // Keep the best move of the position when the new entry has none
+        let previous = if deep_key == key { deep } else { self.probe(key, ply) };
+        let best_move = best_move.or(previous.and_then(|entry| entry.best_move));
+
+        let entry = TtEntry { best_move, score: score_to_tt(score, ply), depth, bound, age };
+        let replace_deep = match deep {
+            Some(deep) => deep_key == key || deep.age != age || depth >= deep.depth,
+            None => true,
+        };
+
+        bucket.slots[if replace_deep { 0 } else { 1 }].store(key, entry.pack());
+    }
+
+    // Returns the permille of slots used in the current search, sampled
+    // from the first thousand buckets
+    pub fn hashfull(&self) -> usize {
+        let age = self.age.load(Ordering::Relaxed);
+        let sample = &self.buckets[..self.buckets.len().min(1000)];
+        let used = sample
+            .iter()
+            .flat_map(|bucket| &bucket.slots)
+            .filter(|slot| TtEntry::unpack(slot.load().1).is_some_and(|entry| entry.age == age))
+            .count();
+
+        used * 1000 / (sample.len() * 2)
+    }
+}
+
+// Mate scores are stored as the distance from the stored position, not
+// from the root, so that they stay correct when reached at another ply
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score > MATE_BOUND {
+        score + ply as i32
+    } else if score < -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score > MATE_BOUND {
+        score - ply as i32
+    } else if score < -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::Bitboard;
+    use crate::search::MATE;
+
+    fn some_move() -> Move {
+        Move::new(52, 36, Bitboard::WHITE_PAWNS, None, Move::DOUBLE_PUSH)
+    }
+
+    #[test]
+    fn stores_and_probes_entries() {
+        let tt = TranspositionTable::new(1);
+        assert_eq!(tt.probe(0xABCD, 0), None);
+
+        tt.store(0xABCD, Some(some_move()), -150, 7, Bound::Lower, 3);
+        let entry = tt.probe(0xABCD, 5).unwrap();
+
+        assert_eq!(entry.best_move, Some(some_move()));
+        assert_eq!((entry.score, entry.depth, entry.bound), (-150, 7, Bound::Lower));
+        assert_eq!(tt.probe(0xABCE, 0), None);
+    }
+
+    #[test]
+    fn adjusts_mate_scores() {
+        let tt = TranspositionTable::new(1);
+
+        // Mate in 3 plies found at ply 4 is mate in 5 plies from ply 2
+        tt.store(1, None, MATE - 7, 3, Bound::Exact, 4);
+        assert_eq!(tt.probe(1, 2).unwrap().score, MATE - 5);
+
+        tt.store(2, None, -MATE + 6, 3, Bound::Exact, 2);
+        assert_eq!(tt.probe(2, 4).unwrap().score, -MATE + 8);
+        assert_eq!(tt.probe(2, 2).unwrap().score, -MATE + 6);
+    }
+
+    #[test]
+    fn replaces_by_depth_and_age() {
+        let tt = TranspositionTable::new(1);
+        let buckets = tt.buckets.len() as u64;
+        // Keys mapping to the first bucket
+        let (deep, shallow, newer) = (1, 2, 3);
+        assert!([deep, shallow, newer]
+            .iter()
+            .all(|&key| (key as u128 * buckets as u128) >> 64 == 0));
+
+        tt.store(deep, None, 10, 8, Bound::Exact, 0);
+        tt.store(shallow, None, 20, 2, Bound::Exact, 0);
+        assert_eq!(tt.probe(deep, 0).unwrap().depth, 8);
+        assert_eq!(tt.probe(shallow, 0).unwrap().depth, 2);
+
+        // Shallow entries keep replacing the second slot
+        tt.store(newer, None, 30, 3, Bound::Exact, 0);
+        assert!(tt.probe(deep, 0).is_some());
+        assert!(tt.probe(shallow, 0).is_none());
+
+        // Deep entries from previous searches give way
+        tt.new_search();
+        tt.store(shallow, None, 20, 1, Bound::Exact, 0);
+        assert!(tt.probe(deep, 0).is_none());
+        assert_eq!(tt.probe(shallow, 0).unwrap().age, 1);
+    }
+
+    #[test]
+    fn keeps_best_move_of_position() {
+        let tt = TranspositionTable::new(1);
+
+        tt.store(42, Some(some_move()), 10, 4, Bound::Lower, 0);
+        tt.store(42, None, 5, 5, Bound::Upper, 0);
+        assert_eq!(tt.probe(42, 0).unwrap().best_move, Some(some_move()));
+    }
+
+    #[test]
+    fn clears_and_sizes_table() {
+        let tt = TranspositionTable::new(2);
+        assert_eq!(tt.size_mb(), 2);
+        assert_eq!(tt.hashfull(), 0);
+
+        for key in 0..2000u64 {
+            tt.store(key.wrapping_mul(0x9E37_79B9_7F4A_7C15), None, 0, 1, Bound::Exact, 0);
+        }
+        assert!(tt.hashfull() > 0);
+
+        tt.clear();
+        assert_eq!(tt.hashfull(), 0);
+        assert_eq!(tt.probe(0x9E37_79B9_7F4A_7C15, 0), None);
+    }
+}
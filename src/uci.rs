@@ -0,0 +1,645 @@
+use crate::constants::STARTING_FEN;
+use crate::defs::Side;
+use crate::eval;
+use crate::position::Position;
+use crate::search::{Search, SearchLimits, SearchResult, MAX_MULTIPV, MAX_THREADS};
+use crate::time::{Clock, DEFAULT_MOVE_OVERHEAD, MAX_MOVE_OVERHEAD};
+use crate::tt::{TranspositionTable, DEFAULT_HASH_MB, MAX_HASH_MB};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Universal Chess Interface front-end, reading commands line by line
+// and writing responses to the output
+//
+// https://www.chessprogramming.org/UCI
+pub struct Uci<W: Write + Send + 'static> {
+    out: Arc<Mutex<W>>,
+    position: Position,
+    // Keys of the positions played before the current one
+    keys: Vec<u64>,
+    stop: Arc<AtomicBool>,
+    // Set by "go ponder" until "ponderhit" starts the engine's clock
+    ponder: Arc<AtomicBool>,
+    tt: Arc<TranspositionTable>,
+    threads: usize,
+    // Number of best lines reported during the search
+    multipv: usize,
+    // Time kept in reserve to avoid losing on time because of communication delays
+    move_overhead: Duration,
+    // Castling moves are sent and received as the king capturing its rook
+    chess960: bool,
+    search: Option<JoinHandle<()>>,
+}
+
+// Runs the UCI loop on the standard input and output
+pub fn run() {
+    let mut uci = Uci::new(io::stdout());
+
+    for line in io::stdin().lock().lines() {
+        match line {
+            Ok(line) if uci.handle(&line) => (),
+            _ => break,
+        }
+    }
+
+    uci.stop_search();
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out: Arc::new(Mutex::new(out)),
+            position: Position::from_fen(STARTING_FEN).unwrap(),
+            keys: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            ponder: Arc::new(AtomicBool::new(false)),
+            tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+            threads: 1,
+            multipv: 1,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            chess960: false,
+            search: None,
+        }
+    }
+
+    // Handles a single command, returns false when the engine should quit
+    pub fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match tokens.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return true,
+        };
+
+        match command {
+            "uci" => {
+                send(&self.out, "id name rschess");
+                send(&self.out, "id author Michal Jabczyk");
+                send(
+                    &self.out,
+                    format!(
+                        "option name Hash type spin default {} min 1 max {}",
+                        DEFAULT_HASH_MB, MAX_HASH_MB
+                    ),
+                );
+                send(
+                    &self.out,
+                    format!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS),
+                );
+                send(
+                    &self.out,
+                    format!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTIPV),
+                );
+                send(
+                    &self.out,
+                    format!(
+                        "option name MoveOverhead type spin default {} min 0 max {}",
+                        DEFAULT_MOVE_OVERHEAD.as_millis(),
+                        MAX_MOVE_OVERHEAD.as_millis()
+                    ),
+                );
+                send(&self.out, "option name Ponder type check default false");
+                send(&self.out, "option name UCI_Chess960 type check default false");
+                send(&self.out, "uciok");
+            }
+            "isready" => send(&self.out, "readyok"),
+            "ucinewgame" => {
+                self.stop_search();
+                self.tt.clear();
+                self.set_position(&["startpos"]);
+            }
+            "position" => {
+                self.stop_search();
+                self.set_position(args);
+            }
+            "go" => {
+                self.stop_search();
+                self.go(args);
+            }
+            "ponderhit" => self.ponder.store(false, Ordering::Relaxed),
+            "stop" => self.stop_search(),
+            "setoption" => self.set_option(args),
+            "d" => send(&self.out, self.position.board_string()),
+            "eval" => send(&self.out, eval::trace(&self.position)),
+            "quit" => {
+                self.stop_search();
+                return false;
+            }
+            _ => send(&self.out, format!("info string unknown command {}", command)),
+        }
+
+        true
+    }
+
+    // Handles "position [startpos | fen <fen>] [moves <move>...]"
+    fn set_position(&mut self, args: &[&str]) {
+        let moves_index = args.iter().position(|&arg| arg == "moves").unwrap_or(args.len());
+
+        let position = match args.split_first() {
+            Some((&"startpos", _)) => Position::from_fen(STARTING_FEN),
+            Some((&"fen", fen)) => Position::from_fen(&fen[..moves_index - 1].join(" ")),
+            _ => {
+                send(&self.out, "info string expected startpos or fen");
+                return;
+            }
+        };
+
+        let mut position = match position {
+            Ok(position) => position,
+            Err(err) => {
+                send(&self.out, format!("info string invalid fen: {}", err));
+                return;
+            }
+        };
+        // Positions which cannot occur in a game would break the move generator
+        if let Err(errors) = position.validate() {
+            let errors: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+            send(&self.out, format!("info string invalid position: {}", errors.join(", ")));
+            return;
+        }
+        position.chess960 |= self.chess960;
+
+        let mut keys = Vec::new();
+        for move_str in args.iter().skip(moves_index + 1) {
+            match position.parse_uci_move(move_str) {
+                Ok(m) => {
+                    keys.push(position.key);
+                    position.make_move(m);
+                }
+                Err(err) => {
+                    send(&self.out, format!("info string {}", err));
+                    return;
+                }
+            }
+        }
+
+        self.position = position;
+        self.keys = keys;
+    }
+
+    // Handles "go" with its search limits, or "go perft <depth>"
+    fn go(&mut self, args: &[&str]) {
+        let mut limits = SearchLimits::default();
+        let (mut infinite, mut ponder) = (false, false);
+        let (mut time, mut clock) = (None, Clock::default());
+        let (time_arg, increment_arg) = match self.position.side_to_move {
+            Side::White => ("wtime", "winc"),
+            Side::Black => ("btime", "binc"),
+        };
+
+        let mut iter = args.iter();
+        while let Some(&arg) = iter.next() {
+            let mut value = || iter.next().and_then(|value| value.parse::<u64>().ok());
+
+            match arg {
+                "depth" => limits.depth = value().map(|depth| depth as u32),
+                "nodes" => limits.nodes = value(),
+                "movetime" => limits.movetime = value().map(Duration::from_millis),
+                "movestogo" => clock.moves_to_go = value().map(|moves| moves as u32),
+                "infinite" => infinite = true,
+                "ponder" => ponder = true,
+                "perft" => return self.perft(value().unwrap_or(1) as u32),
+                _ if arg == time_arg => time = iter.next().and_then(|value| clock_millis(value)),
+                _ if arg == increment_arg => {
+                    clock.increment =
+                        iter.next().and_then(|value| clock_millis(value)).unwrap_or_default()
+                }
+                _ => (),
+            }
+        }
+
+        if let (Some(time), false) = (time, infinite) {
+            limits.clock = Some(Clock { time, ..clock });
+        }
+
+        self.stop.store(false, Ordering::Relaxed);
+        self.ponder.store(ponder, Ordering::Relaxed);
+        let (out, stop, ponder) = (self.out.clone(), self.stop.clone(), self.ponder.clone());
+        let mut search = Search::new(&self.position, limits)
+            .with_stop(stop.clone())
+            .with_ponder(ponder.clone())
+            .with_tt(self.tt.clone())
+            .with_threads(self.threads)
+            .with_multipv(self.multipv)
+            .with_move_overhead(self.move_overhead)
+            .with_history(self.keys.clone());
+
+        self.search = Some(thread::spawn(move || {
+            let result = search.run(|result| send(&out, format_info(result)));
+
+            // In infinite mode and while pondering the best move must not be sent
+            // before "stop", which is also how a ponder miss is handled
+            while (infinite || ponder.load(Ordering::Relaxed)) && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            // The expected reply is suggested to ponder on
+            match (result.best_move, result.pv.get(1)) {
+                (Some(m), Some(reply)) => {
+                    send(&out, format!("bestmove {} ponder {}", m.to_uci(), reply.to_uci()))
+                }
+                (Some(m), None) => send(&out, format!("bestmove {}", m.to_uci())),
+                (None, _) => send(&out, "bestmove 0000"),
+            }
+        }));
+    }
+
+    fn perft(&mut self, depth: u32) {
+        let mut position = self.position.clone();
+        let divide = position.divide(depth.max(1));
+
+        for (m, nodes) in &divide {
+            send(&self.out, format!("{}: {}", m, nodes));
+        }
+        send(
+            &self.out,
+            format!("\nNodes searched: {}", divide.iter().map(|(_, n)| n).sum::<u64>()),
+        );
+    }
+
+    // Handles "setoption name <name> [value <value>]"
+    fn set_option(&mut self, args: &[&str]) {
+        let value_index = args.iter().position(|&arg| arg == "value").unwrap_or(args.len());
+        let name = args.get(1..value_index).unwrap_or_default().join(" ");
+        let value = args.get(value_index + 1..).unwrap_or_default().join(" ");
+
+        match name.as_str() {
+            "Hash" => match value.parse::<usize>() {
+                Ok(megabytes) if (1..=MAX_HASH_MB).contains(&megabytes) => {
+                    self.stop_search();
+                    self.tt = Arc::new(TranspositionTable::new(megabytes));
+                }
+                _ => send(&self.out, format!("info string invalid Hash value {}", value)),
+            },
+            "Threads" => match value.parse::<usize>() {
+                Ok(threads) if (1..=MAX_THREADS).contains(&threads) => self.threads = threads,
+                _ => send(&self.out, format!("info string invalid Threads value {}", value)),
+            },
+            "MultiPV" => match value.parse::<usize>() {
+                Ok(lines) if (1..=MAX_MULTIPV).contains(&lines) => self.multipv = lines,
+                _ => send(&self.out, format!("info string invalid MultiPV value {}", value)),
+            },
+            "MoveOverhead" => match value.parse::<u64>().map(Duration::from_millis) {
+                Ok(overhead) if overhead <= MAX_MOVE_OVERHEAD => self.move_overhead = overhead,
+                _ => send(&self.out, format!("info string invalid MoveOverhead value {}", value)),
+            },
+            // The GUI decides when to ponder, so the option only needs validating
+            "Ponder" => match value.as_str() {
+                "true" | "false" => (),
+                _ => send(&self.out, format!("info string invalid Ponder value {}", value)),
+            },
+            "UCI_Chess960" => match value.as_str() {
+                "true" | "false" => self.chess960 = value == "true",
+                _ => send(&self.out, format!("info string invalid UCI_Chess960 value {}", value)),
+            },
+            _ => send(&self.out, format!("info string unknown option {}", name)),
+        }
+    }
+
+    // Stops the running search and waits for its best move
+    pub fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(search) = self.search.take() {
+            search.join().unwrap();
+        }
+    }
+}
+
+fn send<W: Write>(out: &Mutex<W>, message: impl std::fmt::Display) {
+    let mut out = out.lock().unwrap();
+    writeln!(out, "{}", message).and_then(|_| out.flush()).unwrap();
+}
+
+// Parses a clock time, which some GUIs send negative once the engine is over time
+fn clock_millis(value: &str) -> Option<Duration> {
+    value.parse::<i64>().ok().map(|millis| Duration::from_millis(millis.max(0) as u64))
+}
+
+fn format_info(result: &SearchResult) -> String {
+    let score = match result.mate_in() {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", result.score),
+    };
+    let millis = result.time.as_millis() as u64;
+    let pv: Vec<String> = result.pv.iter().map(|m| m.to_string()).collect();
+
+    format!(
+        "info depth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+        result.depth,
+        result.multipv,
+        score,
+        result.nodes,
+        result.nodes * 1000 / millis.max(1),
+        millis,
+        pv.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect()
+        }
+    }
+
+    fn run_commands(commands: &[&str]) -> (Uci<Buffer>, Buffer) {
+        let buffer = Buffer::default();
+        let mut uci = Uci::new(buffer.clone());
+
+        for command in commands {
+            uci.handle(command);
+        }
+
+        (uci, buffer)
+    }
+
+    #[test]
+    fn identifies_engine() {
+        let (_, out) = run_commands(&["uci", "isready"]);
+
+        assert_eq!(out.lines()[0], "id name rschess");
+        assert_eq!(out.lines()[out.lines().len() - 2..], ["uciok", "readyok"]);
+    }
+
+    #[test]
+    fn sets_position() {
+        let (uci, _) = run_commands(&["position startpos moves e2e4 e7e5 g1f3"]);
+        assert_eq!(
+            uci.position.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        assert_eq!(uci.keys.len(), 3);
+
+        let (uci, _) = run_commands(&["position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 moves e2e4"]);
+        assert_eq!(uci.position.to_fen(), "4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1");
+
+        let (uci, _) = run_commands(&["position fen 4k3/8/8/8/8/8/8/4K3 b - -"]);
+        assert_eq!(uci.position.to_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn rejects_invalid_positions() {
+        let (uci, out) = run_commands(&[
+            "position fen 8/8/8/8/8/8/8/8 w - - 0 1",
+            "position startpos moves e2e5",
+        ]);
+
+        assert_eq!(uci.position.to_fen(), STARTING_FEN);
+        assert_eq!(out.lines()[0], "info string invalid fen: White king is missing");
+        assert_eq!(out.lines()[1], "info string illegal move \"e2e5\"");
+    }
+
+    #[test]
+    fn rejects_impossible_positions() {
+        let (mut uci, out) = run_commands(&[
+            "position fen P3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "position fen 4k3/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+        ]);
+
+        assert_eq!(uci.position.to_fen(), STARTING_FEN);
+        assert_eq!(
+            out.lines(),
+            [
+                "info string invalid position: pawn on back rank square a8",
+                "info string invalid position: castling right 4 without rook on its home square, \
+                 castling right 8 without rook on its home square",
+            ]
+        );
+
+        uci.handle("go depth 1");
+        uci.search.take().unwrap().join().unwrap();
+        assert!(out.lines().last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn searches_to_depth() {
+        let (mut uci, out) =
+            run_commands(&["position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "go depth 2"]);
+        uci.search.take().unwrap().join().unwrap();
+
+        let lines = out.lines();
+        assert!(lines[0].starts_with("info depth 1 multipv 1 score"));
+        assert!(lines[1].starts_with("info depth 2 multipv 1 score mate 1 nodes"));
+        assert_eq!(lines[2], "bestmove a1a8");
+    }
+
+    #[test]
+    fn stops_infinite_search() {
+        let (mut uci, out) = run_commands(&["position startpos", "go infinite"]);
+        thread::sleep(Duration::from_millis(20));
+        uci.handle("stop");
+
+        assert!(uci.search.is_none());
+        assert!(out.lines().last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn searches_on_clock() {
+        let (mut uci, out) =
+            run_commands(&["position startpos", "go wtime 1000 btime 1000 winc 0"]);
+        uci.search.take().unwrap().join().unwrap();
+
+        assert!(out.lines().last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn ponders_until_ponderhit() {
+        let (mut uci, out) = run_commands(&[
+            "uci",
+            "setoption name Ponder value true",
+            "position startpos moves e2e4",
+            "go ponder wtime 100 btime 100",
+        ]);
+        assert!(out.lines().contains(&"option name Ponder type check default false".into()));
+
+        // The clock of 100ms would have run out without pondering
+        thread::sleep(Duration::from_millis(200));
+        assert!(!out.lines().last().unwrap().starts_with("bestmove "));
+
+        uci.handle("ponderhit");
+        uci.search.take().unwrap().join().unwrap();
+        let bestmove = out.lines().last().unwrap().clone();
+        assert!(bestmove.starts_with("bestmove ") && bestmove.contains(" ponder "), "{}", bestmove);
+    }
+
+    #[test]
+    fn stops_pondering_on_miss() {
+        let (mut uci, out) = run_commands(&["position startpos", "go ponder wtime 100 btime 100"]);
+        thread::sleep(Duration::from_millis(20));
+        uci.handle("stop");
+
+        assert!(uci.search.is_none());
+        assert!(out.lines().last().unwrap().starts_with("bestmove "));
+
+        // The search of the move actually played starts afresh
+        uci.handle("position startpos moves e2e4");
+        uci.handle("go depth 2");
+        uci.search.take().unwrap().join().unwrap();
+        assert!(out.lines().last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn moves_at_once_when_over_time() {
+        let (mut uci, out) =
+            run_commands(&["position startpos", "go wtime -100 btime 1000 winc -5"]);
+        thread::sleep(Duration::from_millis(200));
+
+        assert!(out.lines().last().unwrap().starts_with("bestmove "));
+        uci.search.take().unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn sets_move_overhead() {
+        let (uci, out) = run_commands(&[
+            "uci",
+            "setoption name MoveOverhead value 200",
+            "setoption name MoveOverhead value 6000",
+        ]);
+
+        assert!(out
+            .lines()
+            .contains(&"option name MoveOverhead type spin default 50 min 0 max 5000".into()));
+        assert!(out.lines().contains(&"info string invalid MoveOverhead value 6000".into()));
+        assert_eq!(uci.move_overhead, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn runs_perft() {
+        let (_, out) = run_commands(&["position startpos", "go perft 2"]);
+
+        assert_eq!(out.lines().len(), 22);
+        assert_eq!(out.lines().last().unwrap(), "Nodes searched: 400");
+    }
+
+    #[test]
+    fn prints_board() {
+        let (_, out) = run_commands(&["position startpos moves e2e4", "d"]);
+        let lines = out.lines();
+
+        assert_eq!(lines[0], "0  r n b q k b n r");
+        assert_eq!(lines[4], "4  . . . . P . . .");
+        assert_eq!(lines.last().unwrap(), "Side to move: Black");
+    }
+
+    #[test]
+    fn prints_evaluation() {
+        let (_, out) = run_commands(&["position startpos", "eval"]);
+        assert_eq!(out.lines().last().unwrap(), "Phase: 24/24  Score: 0 (side to move)");
+    }
+
+    #[test]
+    fn sets_hash_size() {
+        let (uci, out) = run_commands(&["uci", "setoption name Hash value 2"]);
+        assert!(out
+            .lines()
+            .contains(&"option name Hash type spin default 16 min 1 max 65536".into()));
+        assert_eq!(uci.tt.size_mb(), 2);
+
+        let (uci, out) = run_commands(&["setoption name Hash value 0", "setoption name Foo"]);
+        assert_eq!(uci.tt.size_mb(), DEFAULT_HASH_MB);
+        assert_eq!(
+            out.lines(),
+            ["info string invalid Hash value 0", "info string unknown option Foo"]
+        );
+    }
+
+    #[test]
+    fn searches_with_threads() {
+        let (mut uci, out) =
+            run_commands(&["uci", "setoption name Threads value 3", "setoption name Threads"]);
+        assert!(out
+            .lines()
+            .contains(&"option name Threads type spin default 1 min 1 max 256".into()));
+        assert!(out.lines().contains(&"info string invalid Threads value ".into()));
+        assert_eq!(uci.threads, 3);
+
+        uci.handle("go depth 3");
+        uci.search.take().unwrap().join().unwrap();
+        assert!(out.lines().last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn reports_multiple_lines() {
+        let (mut uci, out) = run_commands(&[
+            "uci",
+            "setoption name MultiPV value 3",
+            "setoption name MultiPV value 0",
+            "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            "go depth 2",
+        ]);
+        uci.search.take().unwrap().join().unwrap();
+
+        let lines = out.lines();
+        assert!(lines.contains(&"option name MultiPV type spin default 1 min 1 max 256".into()));
+        assert!(lines.contains(&"info string invalid MultiPV value 0".into()));
+
+        let infos: Vec<&String> =
+            lines.iter().filter(|line| line.starts_with("info depth 2")).collect();
+        assert_eq!(infos.len(), 3);
+        for (index, info) in infos.iter().enumerate() {
+            assert!(info.starts_with(&format!("info depth 2 multipv {} score", index + 1)));
+        }
+        assert!(infos[0].contains("score mate 1"));
+        assert_eq!(lines.last().unwrap(), "bestmove a1a8");
+    }
+
+    #[test]
+    fn plays_chess960() {
+        let (uci, out) = run_commands(&[
+            "uci",
+            "setoption name UCI_Chess960 value true",
+            "position fen rk4r1/6p1/8/8/8/8/6P1/RK4R1 w GAga - 0 1 moves b1a1",
+        ]);
+
+        assert!(out.lines().contains(&"option name UCI_Chess960 type check default false".into()));
+        assert_eq!(uci.position.to_fen(), "rk4r1/6p1/8/8/8/8/6P1/2KR2R1 b kq - 1 1");
+
+        // Castling in the standard start position is sent as king takes rook as well
+        let (uci, _) = run_commands(&[
+            "setoption name UCI_Chess960 value true",
+            "position startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1h1",
+        ]);
+        assert_eq!(uci.position.piece_at(62), Some(crate::defs::Bitboard::WHITE_KING));
+    }
+
+    #[test]
+    fn clears_hash_on_new_game() {
+        let (mut uci, _) = run_commands(&["position startpos", "go depth 3"]);
+        uci.search.take().unwrap().join().unwrap();
+        let key = uci.position.key;
+        assert!(uci.tt.probe(key, 0).is_some());
+
+        uci.handle("ucinewgame");
+        assert!(uci.tt.probe(key, 0).is_none());
+    }
+
+    #[test]
+    fn quits() {
+        let (mut uci, _) = run_commands(&[]);
+
+        assert!(uci.handle("isready"));
+        assert!(!uci.handle("quit"));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::bitboard::*;
+use crate::constants::{HUMAN_SQUARES, RANK_1, RANK_8};
+use crate::defs::{Bitboard, CastlingRights, Side, Square};
+use crate::movegen::{en_passant_victim, CASTLING_MOVES};
+use crate::position::Position;
+use std::{error, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionError {
+    // Piece bitboards overlap or do not add up to the side bitboards
+    InconsistentBitboards,
+    KingCount { side: Side, count: u32 },
+    PawnOnBackRank(u8),
+    TooManyPawns(Side),
+    TooManyPieces(Side),
+    // The side which has just moved left its king in check
+    OpponentInCheck,
+    CastlingWithoutKing(CastlingRights),
+    CastlingWithoutRook(CastlingRights),
+    BadEnPassant(u8),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionError::InconsistentBitboards => write!(f, "inconsistent bitboards"),
+            PositionError::KingCount { side, count } => {
+                write!(f, "{} has {} kings instead of 1", side, count)
+            }
+            PositionError::PawnOnBackRank(square) => {
+                write!(f, "pawn on back rank square {}", HUMAN_SQUARES[*square as usize])
+            }
+            PositionError::TooManyPawns(side) => write!(f, "{} has more than 8 pawns", side),
+            PositionError::TooManyPieces(side) => write!(f, "{} has more than 16 pieces", side),
+            PositionError::OpponentInCheck => write!(f, "side not to move is in check"),
+            PositionError::CastlingWithoutKing(right) => {
+                write!(f, "castling right {} without king on its home square", right)
+            }
+            PositionError::CastlingWithoutRook(right) => {
+                write!(f, "castling right {} without rook on its home square", right)
+            }
+            PositionError::BadEnPassant(square) => {
+                write!(f, "invalid en passant square {}", HUMAN_SQUARES[*square as usize])
+            }
+        }
+    }
+}
+
+impl error::Error for PositionError {}
+
+impl Position {
+    // Checks whether the position could occur in a legal game,
+    // returns all the problems found otherwise
+    pub fn validate(&self) -> Result<(), Vec<PositionError>> {
+        let mut errors = Vec::new();
+
+        self.validate_bitboards(&mut errors);
+        self.validate_material(&mut errors);
+        self.validate_check(&mut errors);
+        self.validate_castling_rights(&mut errors);
+        self.validate_en_passant(&mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn validate_bitboards(&self, errors: &mut Vec<PositionError>) {
+        let total: u32 = self.bitboards[0..12].iter().map(|&bitboard| count_bits(bitboard)).sum();
+        let white = sum_bitboards(&self.bitboards[0..6]);
+        let black = sum_bitboards(&self.bitboards[6..12]);
+
+        if total != count_bits(white | black)
+            || self.bitboards[Bitboard::WHITE_PIECES] != white
+            || self.bitboards[Bitboard::BLACK_PIECES] != black
+            || self.bitboards[Bitboard::ALL_PIECES] != white | black
+        {
+            errors.push(PositionError::InconsistentBitboards);
+        }
+    }
+
+    fn validate_material(&self, errors: &mut Vec<PositionError>) {
+        for &side in &[Side::White, Side::Black] {
+            let offset = side.piece_offset();
+
+            let kings = count_bits(self.bitboards[Bitboard::WHITE_KING + offset]);
+            if kings != 1 {
+                errors.push(PositionError::KingCount { side, count: kings });
+            }
+            if count_bits(self.bitboards[Bitboard::WHITE_PAWNS + offset]) > 8 {
+                errors.push(PositionError::TooManyPawns(side));
+            }
+            if count_bits(self.bitboards[side.pieces_index()]) > 16 {
+                errors.push(PositionError::TooManyPieces(side));
+            }
+        }
+
+        let pawns = self.bitboards[Bitboard::WHITE_PAWNS] | self.bitboards[Bitboard::BLACK_PAWNS];
+        let mut back_rank_pawns = pawns & (RANK_1 | RANK_8);
+        while back_rank_pawns != 0 {
+            errors.push(PositionError::PawnOnBackRank(pop_ls1b(&mut back_rank_pawns)));
+        }
+    }
+
+    fn validate_check(&self, errors: &mut Vec<PositionError>) {
+        let opponent = !self.side_to_move;
+        let king = self.bitboards[Bitboard::WHITE_KING + opponent.piece_offset()];
+
+        if count_bits(king) == 1 && self.is_square_attacked(get_ls1b_index(king), self.side_to_move)
+        {
+            errors.push(PositionError::OpponentInCheck);
+        }
+    }
+
+    fn validate_castling_rights(&self, errors: &mut Vec<PositionError>) {
+        for (index, castling) in CASTLING_MOVES.iter().enumerate() {
+            if self.castling_rights & castling.right == 0 {
+                continue;
+            }
+
+            // The king may start on any square of the back rank in Chess960
+            let offset = castling.side.piece_offset();
+            let back_rank = if castling.side == Side::White { RANK_1 } else { RANK_8 };
+            if self.bitboards[Bitboard::WHITE_KING + offset] & back_rank == 0 {
+                errors.push(PositionError::CastlingWithoutKing(castling.right));
+            }
+
+            let rook = self.castling_rooks[index];
+            if get_bit(self.bitboards[Bitboard::WHITE_ROOKS + offset] & back_rank, rook) == 0 {
+                errors.push(PositionError::CastlingWithoutRook(castling.right));
+            }
+        }
+    }
+
+    // En passant square has to be empty, on the 3rd rank behind a white pawn
+    // or on the 6th rank behind a black pawn, with the pawn's origin empty
+    fn validate_en_passant(&self, errors: &mut Vec<PositionError>) {
+        let square = self.en_passant_square;
+        if square == Square::NoSquare as u8 {
+            return;
+        }
+
+        let side = self.side_to_move;
+        let expected_rank = match side {
+            Side::White => 2,
+            Side::Black => 5,
+        };
+
+        let valid = coords(square).0 == expected_rank && {
+            let pawn = en_passant_victim(side, square);
+            let origin = 2 * square - pawn;
+            let occupancy = self.bitboards[Bitboard::ALL_PIECES];
+
+            get_bit(self.bitboards[Bitboard::WHITE_PAWNS + (!side).piece_offset()], pawn) != 0
+                && get_bit(occupancy, square) == 0
+                && get_bit(occupancy, origin) == 0
+        };
+
+        if !valid {
+            errors.push(PositionError::BadEnPassant(square));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::{Castling, Piece, Square::*};
+
+    fn errors(fen: &str) -> Vec<PositionError> {
+        Position::from_fen(fen).unwrap().validate().err().unwrap_or_default()
+    }
+
+    #[test]
+    fn accepts_valid_positions() {
+        for fen in &[
+            crate::constants::STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1",
+        ] {
+            assert_eq!(Position::from_fen(fen).unwrap().validate(), Ok(()), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn rejects_king_count() {
+        assert_eq!(
+            errors("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+            vec![PositionError::KingCount { side: Side::White, count: 2 }]
+        );
+
+        let position = Position::from_position(map! { E1 => Piece::WhiteKing });
+        let position = Position { castling_rights: 0, ..position };
+        assert_eq!(
+            position.validate(),
+            Err(vec![PositionError::KingCount { side: Side::Black, count: 0 }])
+        );
+    }
+
+    #[test]
+    fn rejects_pawns_on_back_rank() {
+        assert_eq!(
+            errors("P3k3/8/8/8/8/8/8/4K2p w - - 0 1"),
+            vec![PositionError::PawnOnBackRank(A8 as u8), PositionError::PawnOnBackRank(H1 as u8)]
+        );
+    }
+
+    #[test]
+    fn rejects_too_much_material() {
+        assert_eq!(
+            errors("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1"),
+            vec![PositionError::TooManyPawns(Side::White)]
+        );
+        assert_eq!(
+            errors("rnbqkbnr/pppppppp/n7/8/8/8/8/4K3 w - - 0 1"),
+            vec![PositionError::TooManyPieces(Side::Black)]
+        );
+    }
+
+    #[test]
+    fn rejects_opponent_in_check() {
+        assert_eq!(errors("4k3/8/8/8/8/8/8/4KR2 w - - 0 1"), vec![]);
+        assert_eq!(errors("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"), vec![PositionError::OpponentInCheck]);
+    }
+
+    #[test]
+    fn rejects_castling_rights_without_pieces() {
+        assert_eq!(
+            errors("r3k3/8/8/8/8/8/5K2/R6R w KQkq - 0 1"),
+            vec![
+                PositionError::CastlingWithoutKing(Castling::WK),
+                PositionError::CastlingWithoutKing(Castling::WQ),
+                PositionError::CastlingWithoutRook(Castling::BK),
+            ]
+        );
+        // The king may castle from any back rank square in Chess960
+        assert_eq!(errors("r3k3/8/8/8/8/8/8/R4K1R w KQq - 0 1"), vec![]);
+        assert_eq!(
+            errors("r3k3/8/8/8/8/8/8/R4K1R w Gq - 0 1"),
+            vec![PositionError::CastlingWithoutRook(Castling::WK)]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_en_passant() {
+        // No pawn behind the square
+        assert_eq!(
+            errors("4k3/8/8/8/8/8/8/4K3 w - e6 0 1"),
+            vec![PositionError::BadEnPassant(E6 as u8)]
+        );
+        // Wrong rank for the side to move
+        assert_eq!(
+            errors("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1"),
+            vec![PositionError::BadEnPassant(E3 as u8)]
+        );
+        // Pawn origin occupied
+        assert_eq!(
+            errors("4k3/8/8/8/4P3/8/4N3/4K3 b - e3 0 1"),
+            vec![PositionError::BadEnPassant(E3 as u8)]
+        );
+        assert_eq!(errors("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1"), vec![]);
+    }
+
+    #[test]
+    fn rejects_inconsistent_bitboards() {
+        let mut position = Position::from_fen(crate::constants::STARTING_FEN).unwrap();
+        set_bit(&mut position.bitboards[Bitboard::BLACK_QUEENS], E1 as u8);
+
+        assert_eq!(position.validate(), Err(vec![PositionError::InconsistentBitboards]));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::bitboard::*;
+use crate::defs::{CastlingRights, Side, Square};
+use crate::position::Position;
+use crate::random::Random;
+use std::sync::LazyLock;
+
+// Random keys xor-ed together to identify a position
+//
+// https://www.chessprogramming.org/Zobrist_Hashing
+struct ZobristKeys {
+    pieces: [[u64; 64]; 12],
+    side: u64,
+    castling: [u64; 16],
+    en_passant: [u64; 8],
+}
+
+const ZOBRIST_SEED: u64 = 0x2B0B_2157;
+
+static KEYS: LazyLock<ZobristKeys> = LazyLock::new(|| {
+    let mut random = Random::new(ZOBRIST_SEED);
+    let mut keys =
+        ZobristKeys { pieces: [[0; 64]; 12], side: 0, castling: [0; 16], en_passant: [0; 8] };
+
+    keys.pieces.iter_mut().flatten().for_each(|key| *key = random.next_u64());
+    keys.side = random.next_u64();
+    keys.castling.iter_mut().for_each(|key| *key = random.next_u64());
+    keys.en_passant.iter_mut().for_each(|key| *key = random.next_u64());
+
+    keys
+});
+
+#[inline]
+pub(crate) fn piece_key(piece: usize, square: u8) -> u64 {
+    KEYS.pieces[piece][square as usize]
+}
+
+#[inline]
+pub(crate) fn side_key() -> u64 {
+    KEYS.side
+}
+
+#[inline]
+pub(crate) fn castling_key(rights: CastlingRights) -> u64 {
+    KEYS.castling[rights as usize]
+}
+
+// Only the file of the en passant square is hashed
+#[inline]
+pub(crate) fn en_passant_key(square: u8) -> u64 {
+    if square == Square::NoSquare as u8 {
+        0
+    } else {
+        KEYS.en_passant[coords(square).1 as usize]
+    }
+}
+
+impl Position {
+    // Computes the position hash key from scratch
+    pub fn compute_key(&self) -> u64 {
+        let mut key = 0;
+
+        for piece in 0..12 {
+            let mut bitboard = self.bitboards[piece];
+            while bitboard != 0 {
+                key ^= piece_key(piece, pop_ls1b(&mut bitboard));
+            }
+        }
+
+        if self.side_to_move == Side::Black {
+            key ^= side_key();
+        }
+
+        key ^ castling_key(self.castling_rights) ^ en_passant_key(self.en_passant_square)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::STARTING_FEN;
+    use crate::moves::Move;
+
+    fn play(position: &mut Position, moves: &[&str]) {
+        for m in moves {
+            let m = *position.legal_moves().iter().find(|legal| legal.to_string() == *m).unwrap();
+            position.make_move(m);
+        }
+    }
+
+    #[test]
+    fn computes_key_from_fen() {
+        let position = Position::from_fen(STARTING_FEN).unwrap();
+
+        assert_eq!(position.key, position.compute_key());
+        assert_ne!(position.key, 0);
+    }
+
+    #[test]
+    fn distinguishes_side_castling_and_en_passant() {
+        let keys: Vec<u64> = [
+            "4k3/8/8/8/4P3/8/8/R3K3 w - - 0 1",
+            "4k3/8/8/8/4P3/8/8/R3K3 b - - 0 1",
+            "4k3/8/8/8/4P3/8/8/R3K3 w Q - 0 1",
+            "4k3/8/8/8/4P3/8/8/R3K3 b - e3 0 1",
+        ]
+        .iter()
+        .map(|fen| Position::from_fen(fen).unwrap().key)
+        .collect();
+
+        for (i, key) in keys.iter().enumerate() {
+            assert!(!keys[i + 1..].contains(key));
+        }
+    }
+
+    #[test]
+    fn transpositions_have_equal_keys() {
+        let mut a = Position::from_fen(STARTING_FEN).unwrap();
+        let mut b = a.clone();
+
+        play(&mut a, &["g1f3", "g8f6", "b1c3"]);
+        play(&mut b, &["b1c3", "g8f6", "g1f3"]);
+        assert_eq!(a.key, b.key);
+
+        play(&mut a, &["b8c6", "f3g1", "c6b8", "g1f3"]);
+        assert_eq!(a.key, b.key);
+    }
+
+    #[test]
+    fn updates_key_incrementally() {
+        let mut random = Random::new(7);
+        let mut position = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let mut history: Vec<(Move, _, u64)> = Vec::new();
+
+        for _ in 0..100 {
+            let moves = position.legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+
+            let m = moves[random.next_u64() as usize % moves.len()];
+            let key = position.key;
+            history.push((m, position.make_move(m), key));
+            assert_eq!(position.key, position.compute_key());
+        }
+
+        while let Some((m, undo, key)) = history.pop() {
+            position.unmake_move(m, undo);
+            assert_eq!(position.key, key);
+        }
+    }
+}